@@ -16,49 +16,66 @@
 
 #[macro_use]
 extern crate clap;
+#[macro_use]
+extern crate log;
 use clap::App;
 
+use std::io::Read;
+use std::time::Duration;
+
+use techair::capture::{self, Capture};
+use techair::cmd::meta::Safety;
+use techair::dissect;
+use techair::error::TechAirError;
+use techair::frame::Direction;
+use techair::info::DeviceInfo;
+use techair::retry::RetryPolicy;
+use techair::service::ServicePolicy;
+use techair::session::Acu;
+use techair::transport::{ReplayTransport, Transport};
+use techair::usbmon;
 
-fn parse_supply(m: &clap::ArgMatches) {
+
+fn parse_supply<T: Transport>(acu: &mut Acu<T>, m: &clap::ArgMatches) -> Result<(), TechAirError> {
     match m.subcommand_name() {
         Some("logic")       => {
-            let s = techair::cli::get_measure_voltage(techair::cli::MeasureVoltageType::Logic).unwrap();
+            let s = techair::cli::get_measure_voltage(acu, techair::cli::MeasureVoltageType::Logic)?;
             println!("{:02} V", s);
         },
         Some("peripheral")       => {
-            let s = techair::cli::get_measure_voltage(techair::cli::MeasureVoltageType::Peripheral).unwrap();
+            let s = techair::cli::get_measure_voltage(acu, techair::cli::MeasureVoltageType::Peripheral)?;
             println!("{:02} V", s);
         },
         Some("right-hand")       => {
-            let s = techair::cli::get_measure_voltage(techair::cli::MeasureVoltageType::RightHand).unwrap();
+            let s = techair::cli::get_measure_voltage(acu, techair::cli::MeasureVoltageType::RightHand)?;
             println!("{:02} V", s);
         },
         Some("left-hand")       => {
-            let s = techair::cli::get_measure_voltage(techair::cli::MeasureVoltageType::LeftHand).unwrap();
+            let s = techair::cli::get_measure_voltage(acu, techair::cli::MeasureVoltageType::LeftHand)?;
             println!("{:02} V", s);
         },
         Some("right-foot")       => {
-            let s = techair::cli::get_measure_voltage(techair::cli::MeasureVoltageType::RightFoot).unwrap();
+            let s = techair::cli::get_measure_voltage(acu, techair::cli::MeasureVoltageType::RightFoot)?;
             println!("{:02} V", s);
         },
         Some("left-foot")       => {
-            let s = techair::cli::get_measure_voltage(techair::cli::MeasureVoltageType::LeftFoot).unwrap();
+            let s = techair::cli::get_measure_voltage(acu, techair::cli::MeasureVoltageType::LeftFoot)?;
             println!("{:02} V", s);
         },
         Some("squib")       => {
-            let s = techair::cli::get_measure_voltage(techair::cli::MeasureVoltageType::Squib).unwrap();
+            let s = techair::cli::get_measure_voltage(acu, techair::cli::MeasureVoltageType::Squib)?;
             println!("{:02} V", s);
         },
         Some("battery")       => {
-            let s = techair::cli::get_measure_voltage(techair::cli::MeasureVoltageType::Battery).unwrap();
+            let s = techair::cli::get_measure_voltage(acu, techair::cli::MeasureVoltageType::Battery)?;
             println!("{:02} V", s);
         },
         Some("charging-state")       => {
-            let s = techair::cli::get_charging_state().unwrap();
+            let s = techair::cli::get_charging_state(acu)?;
             println!("{}", s);
         },
         Some("zip-state")       => {
-            let s = techair::cli::get_zip_state().unwrap();
+            let s = techair::cli::get_zip_state(acu)?;
             if s {
                 println!("ZIP Closed");
             } else {
@@ -73,26 +90,26 @@ fn parse_supply(m: &clap::ArgMatches) {
 		println!("Some other subcommand was used");
 	}
     }
+    Ok(())
 }
 
-fn sensor(m: &clap::ArgMatches) {
+fn sensor<T: Transport>(acu: &mut Acu<T>, m: &clap::ArgMatches) -> Result<(), TechAirError> {
     match m.subcommand() {
         ("revision", Some(ms))  => {
-		println!("revision --flag");
 		if ms.is_present("right-hand") {
-                        let (ma,mi) = techair::cli::get_sensor_revision(techair::cli::SensorRevisionType::RightHand).unwrap();
+                        let (ma,mi) = techair::cli::get_sensor_revision(acu, techair::cli::SensorRevisionType::RightHand)?;
                         println!("rev {:03}, {:03}", ma, mi);
 		}
 		if ms.is_present("left-hand") {
-                        let (ma,mi) = techair::cli::get_sensor_revision(techair::cli::SensorRevisionType::LeftHand).unwrap();
+                        let (ma,mi) = techair::cli::get_sensor_revision(acu, techair::cli::SensorRevisionType::LeftHand)?;
                         println!("rev {:03}{:03}", ma, mi);
 		}
 		if ms.is_present("right-foot") {
-                        let (ma,mi) = techair::cli::get_sensor_revision(techair::cli::SensorRevisionType::RightFoot).unwrap();
+                        let (ma,mi) = techair::cli::get_sensor_revision(acu, techair::cli::SensorRevisionType::RightFoot)?;
                         println!("rev {:03}{:03}", ma, mi);
 		}
 		if ms.is_present("left-foot") {
-                        let (ma,mi) = techair::cli::get_sensor_revision(techair::cli::SensorRevisionType::LeftFoot).unwrap();
+                        let (ma,mi) = techair::cli::get_sensor_revision(acu, techair::cli::SensorRevisionType::LeftFoot)?;
                         println!("rev {:03}{:03}", ma, mi);
 		}
 	},
@@ -113,75 +130,207 @@ fn sensor(m: &clap::ArgMatches) {
         // If all subcommands are defined above, anything else is unreachabe!()
 	_ => unreachable!(),
     }
+    Ok(())
 }
 
-fn logs(m: &clap::ArgMatches) {
+fn logs<T: Transport>(acu: &mut Acu<T>, m: &clap::ArgMatches) -> Result<(), TechAirError> {
     match m.subcommand() {
         ("no-of-precrash", Some(_)) => {
-            let s = techair::cli::get_no_of_precrash_logs().unwrap();
+            let s = techair::cli::get_no_of_precrash_logs(acu)?;
             println!("{:?}", s);
         },
         ("no-of-postcrash", Some(_)) => {
-            let s = techair::cli::get_no_of_postcrash_logs().unwrap();
+            let s = techair::cli::get_no_of_postcrash_logs(acu)?;
             println!("{:?}", s);
         },
         ("no-of-errors", Some(_)) => {
-            let s = techair::cli::get_no_of_errors().unwrap();
+            let s = techair::cli::get_no_of_errors(acu)?;
             println!("{:?}", s);
         },
         ("bat-count", Some(_)) => {
-            let s = techair::cli::get_bat_count().unwrap();
+            let s = techair::cli::get_bat_count(acu)?;
             println!("{:?}", s);
         },
         ("error-history", Some(_)) => {
-            let s = techair::cli::get_error_history().unwrap();
+            let s = techair::cli::get_error_history(acu)?;
             println!("{:?}", s);
         },
         ("op-hours", Some(_)) => {
-            let s = techair::cli::get_op_hours().unwrap();
+            let s = techair::cli::get_op_hours(acu)?;
             println!("{:?}", s);
         },
         ("", None) => println!("No subcommand was used"),
         // If all subcommands are defined above, anything else is unreachabe!()
 	_ => unreachable!(),
     }
+    Ok(())
 }
 
-fn main() { //-> Result<(), std::io::Error> {
-    // The YAML file is found relative to the current file, similar to how modules are found
-    let yaml = load_yaml!("cli.yml");
-    let m = App::from(yaml).get_matches();
+fn set<T: Transport>(acu: &mut Acu<T>, m: &clap::ArgMatches) -> Result<(), TechAirError> {
+    let (what, value) = match m.subcommand() {
+        (what, Some(ms)) => (what, ms.value_of("value").unwrap()),
+        _ => {
+            println!("No subcommand was used");
+            return Ok(());
+        },
+    };
+    match what {
+        "serial"        => techair::cli::set_serial_nr(acu, value)?,
+        "customer-info" => techair::cli::set_customer_info(acu, value)?,
+        "service-date"  => {
+            let d = value.parse().map_err(TechAirError::InvalidArgument)?;
+            techair::cli::set_service_date(acu, d)?;
+        },
+        "hw-version"    => {
+            let v = value.parse().map_err(TechAirError::InvalidArgument)?;
+            techair::cli::set_hw_version(acu, v)?;
+        },
+        "ctrl-mode"     => {
+            let m = value.parse().map_err(TechAirError::InvalidArgument)?;
+            techair::cli::set_ctrl_mode(acu, m)?;
+        },
+        // If all subcommands are defined above, anything else is unreachabe!()
+        _ => unreachable!(),
+    }
+    Ok(())
+}
 
-    // ...
- //       let cmd = techair::usbcmd::UsbCmd::try_from([0x00, 0x02, 0x3E, 0x81].to_vec());
-//        techair::usbcmd::print_cmd(&cmd.unwrap());
-//        println!("{:#?}", &cmd.unwrap());
+fn service_status<T: Transport>(acu: &mut Acu<T>, m: &clap::ArgMatches) -> Result<(), TechAirError> {
+    // No built-in limits, clap makes sure at least one is given.
+    let mut policy = ServicePolicy{ max_months: None, max_op_hours: None };
+    for (arg, limit) in [("max-months", &mut policy.max_months), ("max-hours", &mut policy.max_op_hours)] {
+        if let Some(n) = m.value_of(arg) {
+            let n = n.parse::<u32>()
+                .map_err(|e| TechAirError::InvalidArgument(format!("--{} {}: {}", arg, n, e)))?;
+            *limit = Some(n);
+        }
+    }
+    let s = techair::service::service_status(acu, &policy)?;
+    println!("{}", s);
+    Ok(())
+}
+
+fn info<T: Transport>(acu: &mut Acu<T>, m: &clap::ArgMatches) -> Result<(), TechAirError> {
+    let info = DeviceInfo::collect(acu)?;
+    match m.value_of("format") {
+        Some("json") => println!("{}", serde_json::to_string_pretty(&info).unwrap()),
+        Some("yaml") => print!("{}", serde_yaml::to_string(&info).unwrap()),
+        _            => println!("{}", info),
+    }
+    Ok(())
+}
+
+fn list() {
+    let ports = match techair::serial::list_techairs() {
+        Ok(ports) => ports,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        },
+    };
+    for p in ports {
+        println!("{}\t{:04x}:{:04x}\t{}", p.port, p.vid, p.pid,
+                 p.serial_number.as_deref().unwrap_or("-"));
+    }
+}
+
+fn decode(m: &clap::ArgMatches) {
+    let frames = if let Some(path) = m.value_of("file") {
+        match capture::load_capture(path) {
+            Ok(entries) => dissect::dissect_capture(&entries),
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                std::process::exit(1);
+            },
+        }
+    } else {
+        let text = match m.values_of("hex") {
+            Some(words) => words.collect::<Vec<_>>().join(" "),
+            None => {
+                let mut s = String::new();
+                if let Err(e) = std::io::stdin().read_to_string(&mut s) {
+                    eprintln!("stdin: {}", e);
+                    std::process::exit(1);
+                }
+                s
+            },
+        };
+        let bytes = match dissect::parse_hex(&text) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            },
+        };
+        let dir = if m.is_present("request") { Direction::Request } else { Direction::Response };
+        dissect::dissect_bytes(dir, &bytes)
+    };
+    for f in frames {
+        println!("{}", f);
+    }
+}
+
+fn sniff(m: &clap::ArgMatches) {
+    let path = m.value_of("trace").unwrap();
+    let mut chunks = match usbmon::load_usbmon(path) {
+        Ok(chunks) => chunks,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            std::process::exit(1);
+        },
+    };
+    if let Some(d) = m.value_of("device") {
+        let dev = d.split_once('.')
+            .and_then(|(bus, dev)| Some((bus.parse::<u16>().ok()?, dev.parse::<u8>().ok()?)));
+        match dev {
+            Some((bus, dev)) => chunks.retain(|c| c.bus == bus && c.dev == dev),
+            None => {
+                eprintln!("--device {}: expected BUS.DEV, e.g. 3.7", d);
+                std::process::exit(1);
+            },
+        }
+    }
+    for f in dissect::dissect_capture(&usbmon::reassemble(&chunks)) {
+        println!("{}", f);
+    }
+}
 
+fn run<T: Transport>(acu: &mut Acu<T>, m: &clap::ArgMatches) -> Result<(), TechAirError> {
    match m.subcommand() {
 	   ("logs", Some(logs_m)) => {
-                    logs(&logs_m);
+                    logs(acu, logs_m)?;
 	   }
 	   ("supply", Some(supply_m)) => {
-		   parse_supply(&supply_m);
+		   parse_supply(acu, supply_m)?;
 //		   let v = techair::cli::get_supply_voltage(s);
 //		   println!("{:?}", v);
 	   }
 	   ("sensor", Some(sensor_m)) => {
-		   sensor(&sensor_m);
+		   sensor(acu, sensor_m)?;
 	   }
 	   ("customer-info", _)       => {
-		   let s = techair::cli::get_customer_info().unwrap();
+		   let s = techair::cli::get_customer_info(acu)?;
 		   println!("{}", s);
 	   }
 	   ("serial", _)              => {
-		   if let Some(s) = techair::cli::get_serial_nr() {
-                       println!("{}", s);
-                   }
+		   let s = techair::cli::get_serial_nr(acu)?;
+		   println!("{}", s);
 	   }
 	   ("sw-version", _)          => {
-		   let s = techair::cli::get_sw_version().unwrap();
+		   let s = techair::cli::get_sw_version(acu)?;
 		   println!("{}", s);
 	   }
+	   ("hw-version", _)          => {
+		   let v = techair::cli::get_hw_version(acu)?;
+		   println!("{} (raw {})", v, v.raw());
+	   }
+	   ("ctrl-mode", _)           => {
+		   let m = techair::cli::get_ctrl_mode(acu)?;
+		   println!("{}", m);
+	   }
+	   ("set", Some(set_m)) => {
+		   set(acu, set_m)?;
+	   }
 	   ("inflation-type", _)  => {
                    // TODO: move this
 //		   let (sr0, sr1) = techair::cli::get_calib_squib_res();
@@ -192,43 +341,57 @@ fn main() { //-> Result<(), std::io::Error> {
 //                       println!("squib resistor 2 = {:?} Ohms", r);
 //                   }
 
-		   let s = techair::cli::get_inflation_type().unwrap();
+		   let s = techair::cli::get_inflation_type(acu)?;
 		   println!("{}", s);
 	   }
 	   ("service-date", _)    => {
-		   let s = techair::cli::get_service_date().unwrap();
-		   println!("{}", s);
+		   match techair::cli::get_service_date(acu)? {
+			   Some(d) => println!("{}", d),
+			   None    => println!("never serviced"),
+		   }
+	   }
+	   ("service-status", Some(status_m)) => {
+		   service_status(acu, status_m)?;
 	   }
-	   ("operating-modus", _) => {
-		   let s = techair::cli::get_operating_modus().unwrap();
-		   println!("{:?}", s);
+	   ("info", Some(info_m)) => {
+		   info(acu, info_m)?;
+	   }
+	   ("operating-modus", Some(modus_m)) => {
+		   let s = techair::cli::get_operating_modus(acu)?;
+		   if modus_m.is_present("json") {
+			   println!("{}", serde_json::to_string(&s).unwrap());
+		   } else {
+			   println!("{}", s);
+		   }
 	   }
 	   ("firmware", Some(m)) => {
-                   println!("{:?}", m.value_of("upgrade"));
+                   debug!("upgrade = {:?}", m.value_of("upgrade"));
                    let fw_file = techair::crypto::idk().unwrap();
                    //println!("{:?}", fw_file.unwrap().header());
                    //println!("{:?}", fw_file.unwrap().data());
-                 let mut bl = techair::cli::SWUpdateBootloader::new();
-                 if bl.xfer_swupdate_bootloader_hexfile(fw_file).is_err() {
-                     println!("upgrade failed");
+                 {
+                     let mut bl = techair::cli::SWUpdateBootloader::new(acu);
+                     if bl.xfer_swupdate_bootloader_hexfile(fw_file).is_err() {
+                         println!("upgrade failed");
+                     }
                  }
                  println!("clearing post-crash logs..");
-                 techair::cli::clear_postcrash_log().unwrap();
+                 techair::cli::clear_postcrash_log(acu)?;
                  //println!("resetting inflation-type..");
-                 //techair::cli::set_inflation_type(0xFF).unwrap(); // 0xbb - double, 0x44 - single
+                 //techair::cli::set_inflation_type(acu, 0xFF).unwrap(); // 0xbb - double, 0x44 - single
                  println!("setting algorithm default thresholds..");
-                 techair::cli::set_algorithm_default_thresholds().unwrap();
+                 techair::cli::set_algorithm_default_thresholds(acu)?;
                  println!("init algorithms..");
-                 techair::cli::init_algorithm().unwrap();
+                 techair::cli::init_algorithm(acu)?;
                  println!("FW upgrade complete!");
 	   }
 	   ("bootloader-version", Some(_)) => {
-                    let mut bl = techair::cli::SWUpdateBootloader::new();
+                    let mut bl = techair::cli::SWUpdateBootloader::new(acu);
                     let s = bl.get_swupdate_bootloader_version().unwrap();
                     println!("bootloader version = {}", s);
 	   }
 	   ("bootloader-state", Some(_)) => {
-                    let mut bl = techair::cli::SWUpdateBootloader::new();
+                    let mut bl = techair::cli::SWUpdateBootloader::new(acu);
                     let s = bl.get_swupdate_bootloader_state().unwrap();
                     println!("bootloader state = {:?}", s);
 	   }
@@ -237,5 +400,89 @@ fn main() { //-> Result<(), std::io::Error> {
            // If all subcommands are defined above, anything else is unreachabe!()
 	   _ => unreachable!(),
    }
+   Ok(())
+}
 
+fn session<T: Transport>(mut acu: Acu<T>, m: &clap::ArgMatches) {
+    if let Some(n) = m.value_of("retries") {
+        match n.parse::<u32>() {
+            Ok(n) => acu.set_retry_policy(RetryPolicy{ max_attempts: n + 1, ..Default::default() }),
+            Err(e) => {
+                eprintln!("--retries {}: {}", n, e);
+                std::process::exit(1);
+            },
+        }
+    }
+    if let Some(ms) = m.value_of("timeout") {
+        let t = ms.parse::<u64>().map(Duration::from_millis)
+            .map_err(|e| e.to_string())
+            .and_then(|t| acu.techair().set_timeout(t).map_err(|e| e.to_string()));
+        if let Err(e) = t {
+            eprintln!("--timeout {}: {}", ms, e);
+            std::process::exit(1);
+        }
+    }
+    if m.is_present("read-only") {
+        acu.techair().set_safety_limit(Safety::ReadOnly);
+    }
+    if let Some(path) = m.value_of("capture") {
+        match Capture::create(path) {
+            Ok(c) => acu.techair().set_capture(Some(c)),
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                std::process::exit(1);
+            },
+        }
+    }
+    if let Err(e) = run(&mut acu, m) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn main() {
+    // The YAML file is found relative to the current file, similar to how modules are found
+    let yaml = load_yaml!("cli.yml");
+    let m = App::from(yaml).get_matches();
+    techair_cli::init_logging(m.occurrences_of("verbose"));
+
+    if let ("list", _) = m.subcommand() {
+        list();
+        return;
+    }
+    if let ("decode", Some(dm)) = m.subcommand() {
+        decode(dm);
+        return;
+    }
+    if let ("sniff", Some(sm)) = m.subcommand() {
+        sniff(sm);
+        return;
+    }
+
+    if let Some(path) = m.value_of("replay") {
+        match ReplayTransport::open(path) {
+            Ok(link) => session(Acu::with_transport(link), &m),
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                std::process::exit(1);
+            },
+        }
+        return;
+    }
+
+    // one connection, shared by everything the subcommand asks for.
+    let acu = if let Some(port) = m.value_of("port") {
+        Acu::open_port(port)
+    } else if let Some(serial) = m.value_of("serial") {
+        Acu::open_serial(serial)
+    } else {
+        Acu::open()
+    };
+    match acu {
+        Ok(acu) => session(acu, &m),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        },
+    }
 }
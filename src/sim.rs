@@ -0,0 +1,67 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+
+#[macro_use]
+extern crate clap;
+use clap::App;
+
+use std::fs;
+
+use techair::sim::{SimConfig, Simulator};
+use techair::transport::PtyTransport;
+
+
+fn load_config(path: Option<&str>) -> Result<SimConfig, String> {
+    match path {
+        Some(p) => {
+            let s = fs::read_to_string(p).map_err(|e| format!("{}: {}", p, e))?;
+            toml::from_str(&s).map_err(|e| format!("{}: {}", p, e))
+        },
+        None => Ok(SimConfig::default()),
+    }
+}
+
+fn main() {
+    let yaml = load_yaml!("sim.yml");
+    let m = App::from(yaml).get_matches();
+    techair_cli::init_logging(m.occurrences_of("verbose"));
+
+    let cfg = match load_config(m.value_of("config")) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            eprintln!("could not load config {}", e);
+            std::process::exit(1);
+        },
+    };
+
+    // keep the slave end open, reads on the master fail once it closes.
+    let (mut master, slave) = PtyTransport::pair().expect("could not allocate a pty");
+    let path = slave.path().expect("pty has no slave path").to_string();
+    if let Some(link) = m.value_of("link") {
+        let _ = fs::remove_file(link);
+        std::os::unix::fs::symlink(&path, link).expect("could not create link");
+        println!("{} -> {}", link, path);
+    }
+    println!("simulating ACU {} on {}", cfg.serial_nr, path);
+    println!("run e.g. `TECHAIR_PORT={} techair-cli serial`", path);
+
+    let mut sim = Simulator::new(cfg);
+    if let Err(e) = sim.serve(&mut master) {
+        eprintln!("pty closed: {}", e);
+    }
+    drop(slave);
+}
@@ -0,0 +1,52 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+
+use crate::cmd::codec::Field;
+use crate::cmd::math::*;
+use crate::cmd::payload::Payload;
+use crate::error::DecodeError;
+use crate::encoder::{Write, WriteError};
+
+
+fn decode_calibres(v: u16) -> Option<f32> {
+    let r = fixed16_to_double(v);
+    if (-100.0..=100.0).contains(&r) {
+        Some(r)
+    } else {
+        warn!("calibration values returned not valid");
+        None
+    }
+}
+
+// the two squib resistances, 0xffff when not calibrated.
+pub(crate) struct CalibRes;
+
+impl Field<(Option<f32>, Option<f32>)> for CalibRes {
+    fn get(p: &mut Payload) -> Result<(Option<f32>, Option<f32>), DecodeError> {
+        let res1 = decode_calibres(p.u16()?);
+        let res2 = decode_calibres(p.u16()?);
+        Ok((res1, res2))
+    }
+    fn put<W: Write>(res: &(Option<f32>, Option<f32>), w: &mut W) -> Result<(), WriteError> {
+        w.write_all(&res.0.map_or(0xffff, double_to_fixed16).to_be_bytes())?;
+        w.write_all(&res.1.map_or(0xffff, double_to_fixed16).to_be_bytes())
+    }
+    #[cfg(test)]
+    fn sample() -> (Option<f32>, Option<f32>) {
+        (Some(2.5), None)
+    }
+}
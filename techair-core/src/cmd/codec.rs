@@ -0,0 +1,264 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+
+// How the payload of each command is laid out on the wire.
+//
+// Every row of the protocol table names one codec per direction for its
+// field; the codecs below are the building blocks the groups share, the
+// group specific ones live next to their types.
+
+use core::convert::TryFrom;
+use core::marker::PhantomData;
+use core::str;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+#[cfg(test)]
+use alloc::vec;
+
+use crate::cmd::payload::Payload;
+use crate::encoder::{invalid_value, Write, WriteError};
+use crate::error::DecodeError;
+
+
+/// Reads and writes a `T` after the subcommand byte.
+pub(crate) trait Field<T> {
+    fn get(p: &mut Payload) -> Result<T, DecodeError>;
+    fn put<W: Write>(v: &T, w: &mut W) -> Result<(), WriteError>;
+
+    // a value that survives put() and get(), for the table tests.
+    #[cfg(test)]
+    fn sample() -> T;
+}
+
+/// Nothing on the wire, the field is left at its default.
+pub(crate) struct Absent;
+
+impl<T: Default> Field<T> for Absent {
+    fn get(_: &mut Payload) -> Result<T, DecodeError> {
+        Ok(T::default())
+    }
+    fn put<W: Write>(_: &T, _: &mut W) -> Result<(), WriteError> {
+        Ok(())
+    }
+    #[cfg(test)]
+    fn sample() -> T {
+        T::default()
+    }
+}
+
+/// Like `Absent`, but the ACU is known to sometimes send something back.
+pub(crate) struct Ignore;
+
+impl<T: Default> Field<T> for Ignore {
+    fn get(p: &mut Payload) -> Result<T, DecodeError> {
+        if !p.is_empty() {
+            debug!("got data back.. {:?}", p.rest());
+        }
+        Ok(T::default())
+    }
+    fn put<W: Write>(_: &T, _: &mut W) -> Result<(), WriteError> {
+        Ok(())
+    }
+    #[cfg(test)]
+    fn sample() -> T {
+        T::default()
+    }
+}
+
+pub(crate) struct U8;
+
+impl Field<u8> for U8 {
+    fn get(p: &mut Payload) -> Result<u8, DecodeError> {
+        p.u8()
+    }
+    fn put<W: Write>(v: &u8, w: &mut W) -> Result<(), WriteError> {
+        w.write_all(&[*v])
+    }
+    #[cfg(test)]
+    fn sample() -> u8 {
+        0x2a
+    }
+}
+
+pub(crate) struct U16;
+
+impl Field<u16> for U16 {
+    fn get(p: &mut Payload) -> Result<u16, DecodeError> {
+        p.u16()
+    }
+    fn put<W: Write>(v: &u16, w: &mut W) -> Result<(), WriteError> {
+        w.write_all(&v.to_be_bytes())
+    }
+    #[cfg(test)]
+    fn sample() -> u16 {
+        0x1234
+    }
+}
+
+pub(crate) struct Bool;
+
+impl Field<bool> for Bool {
+    fn get(p: &mut Payload) -> Result<bool, DecodeError> {
+        Ok(p.u8()? > 0)
+    }
+    fn put<W: Write>(v: &bool, w: &mut W) -> Result<(), WriteError> {
+        w.write_all(&[*v as u8])
+    }
+    #[cfg(test)]
+    fn sample() -> bool {
+        true
+    }
+}
+
+/// `C` must be there when decoding, `None` encodes to nothing.
+pub(crate) struct Opt<C>(PhantomData<C>);
+
+impl<T, C: Field<T>> Field<Option<T>> for Opt<C> {
+    fn get(p: &mut Payload) -> Result<Option<T>, DecodeError> {
+        Ok(Some(C::get(p)?))
+    }
+    fn put<W: Write>(v: &Option<T>, w: &mut W) -> Result<(), WriteError> {
+        match v {
+            Some(v) => C::put(v, w),
+            None    => Ok(()),
+        }
+    }
+    #[cfg(test)]
+    fn sample() -> Option<T> {
+        Some(C::sample())
+    }
+}
+
+/// Like `Opt`, but too few bytes for `C` decode to `None` instead of failing.
+pub(crate) struct OrNone<C>(PhantomData<C>);
+
+impl<T, C: Field<T>> Field<Option<T>> for OrNone<C> {
+    fn get(p: &mut Payload) -> Result<Option<T>, DecodeError> {
+        let mut q = p.clone();
+        match C::get(&mut q) {
+            Ok(v) => {
+                *p = q;
+                Ok(Some(v))
+            },
+            Err(DecodeError::ShortPayload{ .. }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+    fn put<W: Write>(v: &Option<T>, w: &mut W) -> Result<(), WriteError> {
+        Opt::<C>::put(v, w)
+    }
+    #[cfg(test)]
+    fn sample() -> Option<T> {
+        Some(C::sample())
+    }
+}
+
+/// Whatever is left, at least one byte of it.
+pub(crate) struct Bytes;
+
+impl Field<Vec<u8>> for Bytes {
+    fn get(p: &mut Payload) -> Result<Vec<u8>, DecodeError> {
+        p.need(1)?; // ???
+        Ok(p.rest().to_vec())
+    }
+    fn put<W: Write>(v: &Vec<u8>, w: &mut W) -> Result<(), WriteError> {
+        w.write_all(v)
+    }
+    #[cfg(test)]
+    fn sample() -> Vec<u8> {
+        vec![0xde, 0xad]
+    }
+}
+
+/// UTF-8 up to the end of the payload, `None` if it is not valid UTF-8.
+/// Setters carry a plain `String`, which must be valid.
+pub(crate) struct Text;
+
+impl Field<Option<String>> for Text {
+    fn get(p: &mut Payload) -> Result<Option<String>, DecodeError> {
+        Ok(str::from_utf8(p.rest()).ok().map(|s| s.to_string()))
+    }
+    fn put<W: Write>(v: &Option<String>, w: &mut W) -> Result<(), WriteError> {
+        match v {
+            Some(s) => w.write_all(s.as_bytes()),
+            None    => Ok(()),
+        }
+    }
+    #[cfg(test)]
+    fn sample() -> Option<String> {
+        Some("TA0000001".to_string())
+    }
+}
+
+impl Field<String> for Text {
+    fn get(p: &mut Payload) -> Result<String, DecodeError> {
+        let v = p.rest();
+        str::from_utf8(v).map(|s| s.to_string()).map_err(|_| p.invalid())
+    }
+    fn put<W: Write>(v: &String, w: &mut W) -> Result<(), WriteError> {
+        w.write_all(v.as_bytes())
+    }
+    #[cfg(test)]
+    fn sample() -> String {
+        "TA0000002".to_string()
+    }
+}
+
+/// `Text` with a length byte in front.
+pub(crate) struct CountedText;
+
+// the length byte, text that does not fit it cannot be written.
+fn text_len(s: &str) -> Result<u8, WriteError> {
+    u8::try_from(s.len()).map_err(|_| invalid_value("text longer than its length byte"))
+}
+
+impl Field<Option<String>> for CountedText {
+    fn get(p: &mut Payload) -> Result<Option<String>, DecodeError> {
+        // the len of the data buf which we dont need
+        p.u8()?;
+        <Text as Field<Option<String>>>::get(p)
+    }
+    fn put<W: Write>(v: &Option<String>, w: &mut W) -> Result<(), WriteError> {
+        match v {
+            Some(s) => {
+                w.write_all(&[text_len(s)?])?;
+                w.write_all(s.as_bytes())
+            },
+            None => Ok(()),
+        }
+    }
+    #[cfg(test)]
+    fn sample() -> Option<String> {
+        Some("Rider".to_string())
+    }
+}
+
+impl Field<String> for CountedText {
+    fn get(p: &mut Payload) -> Result<String, DecodeError> {
+        let n = p.u8()? as usize;
+        let v = p.bytes(n)?;
+        str::from_utf8(v).map(|s| s.to_string()).map_err(|_| p.invalid())
+    }
+    fn put<W: Write>(v: &String, w: &mut W) -> Result<(), WriteError> {
+        w.write_all(&[text_len(v)?])?;
+        w.write_all(v.as_bytes())
+    }
+    #[cfg(test)]
+    fn sample() -> String {
+        "Service Shop".to_string()
+    }
+}
@@ -0,0 +1,684 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+use core::str::FromStr;
+
+use crate::cmd::codec::Field;
+use crate::cmd::math::*;
+use crate::cmd::payload::Payload;
+use crate::error::DecodeError;
+use crate::encoder::{Write, WriteError};
+
+
+/// The operating mode of an ACU together with its add-ons.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OpModus {
+    pub mode: OpMode,
+    pub addons: OpAddons,
+}
+
+impl OpModus {
+    pub fn from_raw(mode: u8, addons: u8) -> OpModus {
+        OpModus{
+            mode: OpMode::from_raw(mode),
+            addons: OpAddons::from_bits_retain(addons),
+        }
+    }
+
+    /// (mode, add-ons) bytes as they go on the wire.
+    pub fn to_raw(&self) -> (u8, u8) {
+        (self.mode.to_raw(), self.addons.bits())
+    }
+}
+
+impl fmt::Display for OpModus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}, add-ons: {}", self.mode, self.addons)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for OpModus {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let (mode_raw, addons_raw) = self.to_raw();
+        let mut st = s.serialize_struct("OpModus", 4)?;
+        st.serialize_field("mode", &self.mode)?;
+        st.serialize_field("addons", &self.addons)?;
+        st.serialize_field("mode_raw", &mode_raw)?;
+        st.serialize_field("addons_raw", &addons_raw)?;
+        st.end()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OpMode {
+    Street,
+    Race3S,
+    Race,
+    RaceVestStreet,
+    // a mode byte we do not know, kept so it goes back unchanged.
+    Unknown(u8),
+}
+
+impl OpMode {
+    pub fn from_raw(mode: u8) -> OpMode {
+        match mode {
+            0xaa => OpMode::Street,
+            0xbb => OpMode::Race3S,
+            0xdd => OpMode::Race,
+            0xcc => OpMode::RaceVestStreet,
+            b    => OpMode::Unknown(b),
+        }
+    }
+
+    pub fn to_raw(&self) -> u8 {
+        match self {
+            OpMode::Street         => 0xaa,
+            OpMode::Race3S         => 0xbb,
+            OpMode::Race           => 0xdd,
+            OpMode::RaceVestStreet => 0xcc,
+            OpMode::Unknown(b)     => *b,
+        }
+    }
+
+    /// A short name that stays the same between releases, for scripts.
+    pub fn name(&self) -> &'static str {
+        match self {
+            OpMode::Street         => "street",
+            OpMode::Race3S         => "race_3s",
+            OpMode::Race           => "race",
+            OpMode::RaceVestStreet => "race_vest_street",
+            OpMode::Unknown(_)     => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for OpMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OpMode::Street         => write!(f, "STREET"),
+            OpMode::Race3S         => write!(f, "RACE 3S"),
+            OpMode::Race           => write!(f, "RACE"),
+            OpMode::RaceVestStreet => write!(f, "RACE.VEST.STREET"),
+            OpMode::Unknown(b)     => write!(f, "unknown mode {:#04x}", b),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for OpMode {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(self.name())
+    }
+}
+
+bitflags::bitflags! {
+    /// The add-on byte that comes with the operating mode.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub struct OpAddons: u8 {
+        const SD_CARD = 0x04;
+        const GPS = 0x08;
+        /// Open-loop detection is disabled.
+        const OPEN_LOOP_OFF = 0x10;
+    }
+}
+
+impl OpAddons {
+    /// (flag, script name, description) of every add-on we know.
+    const NAMES: &'static [(OpAddons, &'static str, &'static str)] = &[
+        (OpAddons::SD_CARD, "sd_card", "SD card"),
+        (OpAddons::GPS, "gps", "GPS module"),
+        (OpAddons::OPEN_LOOP_OFF, "open_loop_off", "open-loop detection off"),
+    ];
+
+    /// Script names of the add-ons that are set.
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        OpAddons::NAMES.iter().filter(move |(a, _, _)| self.contains(*a)).map(|(_, n, _)| *n)
+    }
+}
+
+impl fmt::Display for OpAddons {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "none");
+        }
+        let mut sep = "";
+        for (_, _, desc) in OpAddons::NAMES.iter().filter(|(a, _, _)| self.contains(*a)) {
+            write!(f, "{}{}", sep, desc)?;
+            sep = ", ";
+        }
+        let unknown = self.bits() & !OpAddons::all().bits();
+        if unknown != 0 {
+            write!(f, "{}unknown bits {:#04x}", sep, unknown)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for OpAddons {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_seq(self.names())
+    }
+}
+
+/// The hardware revision of an ACU, kept in hundredths as it is on the wire.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct HardwareVersion(u16);
+
+impl HardwareVersion {
+    pub fn from_raw(hundredths: u16) -> HardwareVersion {
+        HardwareVersion(hundredths)
+    }
+
+    pub fn from_f32(v: f32) -> HardwareVersion {
+        HardwareVersion(round(v * 100.0) as u16)
+    }
+
+    pub fn raw(&self) -> u16 {
+        self.0
+    }
+
+    pub fn as_f32(&self) -> f32 {
+        self.0 as f32 / 100.0
+    }
+
+    pub fn major(&self) -> u16 {
+        self.0 / 100
+    }
+
+    pub fn minor(&self) -> u16 {
+        self.0 % 100
+    }
+}
+
+impl fmt::Display for HardwareVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{:02}", self.major(), self.minor())
+    }
+}
+
+/// As displayed, "1.05" rather than a float that may not survive.
+#[cfg(feature = "serde")]
+impl serde::Serialize for HardwareVersion {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(self)
+    }
+}
+
+/// "1", "1.5" or "1.05", at most two decimals.
+impl FromStr for HardwareVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || format!("'{}' is not a hardware version such as 1.05", s);
+        let (major, minor) = s.split_once('.').unwrap_or((s, "0"));
+        if major.is_empty() || minor.is_empty() || minor.len() > 2
+            || !major.bytes().chain(minor.bytes()).all(|b| b.is_ascii_digit()) {
+            return Err(bad());
+        }
+        let scale = if minor.len() == 1 { 10 } else { 1 };
+        let major = major.parse::<u16>().map_err(|_| bad())?;
+        let minor = minor.parse::<u16>().map_err(|_| bad())? * scale;
+        major.checked_mul(100).and_then(|m| m.checked_add(minor))
+            .map(HardwareVersion).ok_or_else(bad)
+    }
+}
+
+/// The control mode byte of an ACU. Nothing documents what the values
+/// mean, so it is only ever shown and taken as the raw byte.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CtrlMode(pub u8);
+
+impl fmt::Display for CtrlMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#04x}", self.0)
+    }
+}
+
+/// The byte in decimal or 0x hex.
+impl FromStr for CtrlMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let m = match s.strip_prefix("0x") {
+            Some(hex) => u8::from_str_radix(hex, 16),
+            None => s.parse::<u8>(),
+        };
+        m.map(CtrlMode).map_err(|e| format!("'{}' is not a control mode: {}", s, e))
+    }
+}
+
+/// What the setters carry: the value on its way to the ACU, and the
+/// status byte it answers with, non-zero when it did not take the value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Setting<T> {
+    Value(T),
+    Status(u8),
+}
+
+// the request side of a setter, the value as `C` writes it.
+pub(crate) struct Set<C>(PhantomData<C>);
+
+impl<T, C: Field<T>> Field<Setting<T>> for Set<C> {
+    fn get(p: &mut Payload) -> Result<Setting<T>, DecodeError> {
+        Ok(Setting::Value(C::get(p)?))
+    }
+    fn put<W: Write>(v: &Setting<T>, w: &mut W) -> Result<(), WriteError> {
+        match v {
+            Setting::Value(v) => C::put(v, w),
+            Setting::Status(_) => Ok(()),
+        }
+    }
+    #[cfg(test)]
+    fn sample() -> Setting<T> {
+        Setting::Value(C::sample())
+    }
+}
+
+// the reply side, just the status byte.
+pub(crate) struct Ack;
+
+impl<T> Field<Setting<T>> for Ack {
+    fn get(p: &mut Payload) -> Result<Setting<T>, DecodeError> {
+        Ok(Setting::Status(p.u8()?))
+    }
+    fn put<W: Write>(v: &Setting<T>, w: &mut W) -> Result<(), WriteError> {
+        match v {
+            Setting::Status(status) => w.write_all(&[*status]),
+            Setting::Value(_) => Ok(()),
+        }
+    }
+    #[cfg(test)]
+    fn sample() -> Setting<T> {
+        Setting::Status(0)
+    }
+}
+
+// software version, fixed-point in tenths.
+pub(crate) struct Version;
+
+impl Field<f32> for Version {
+    fn get(p: &mut Payload) -> Result<f32, DecodeError> {
+        Ok(fixed16_to_double(p.u16()?) * 10.0)
+    }
+    fn put<W: Write>(v: &f32, w: &mut W) -> Result<(), WriteError> {
+        w.write_all(&double_to_fixed16(*v / 10.0).to_be_bytes())
+    }
+    #[cfg(test)]
+    fn sample() -> f32 {
+        3.05
+    }
+}
+
+// the hardware version, the same fixed-point as `Version` on the wire.
+pub(crate) struct HwVersion;
+
+impl Field<HardwareVersion> for HwVersion {
+    fn get(p: &mut Payload) -> Result<HardwareVersion, DecodeError> {
+        Ok(HardwareVersion(p.u16()?))
+    }
+    fn put<W: Write>(v: &HardwareVersion, w: &mut W) -> Result<(), WriteError> {
+        w.write_all(&v.0.to_be_bytes())
+    }
+    #[cfg(test)]
+    fn sample() -> HardwareVersion {
+        HardwareVersion(305)
+    }
+}
+
+pub(crate) struct Mode;
+
+impl Field<CtrlMode> for Mode {
+    fn get(p: &mut Payload) -> Result<CtrlMode, DecodeError> {
+        Ok(CtrlMode(p.u8()?))
+    }
+    fn put<W: Write>(m: &CtrlMode, w: &mut W) -> Result<(), WriteError> {
+        w.write_all(&[m.0])
+    }
+    #[cfg(test)]
+    fn sample() -> CtrlMode {
+        CtrlMode(0x2a)
+    }
+}
+
+pub(crate) struct Modus;
+
+impl Field<OpModus> for Modus {
+    fn get(p: &mut Payload) -> Result<OpModus, DecodeError> {
+        let mode = p.u8()?;
+        let addons = p.u8()?;
+        Ok(OpModus::from_raw(mode, addons))
+    }
+    fn put<W: Write>(m: &OpModus, w: &mut W) -> Result<(), WriteError> {
+        let (mode, addons) = m.to_raw();
+        w.write_all(&[mode, addons])
+    }
+    #[cfg(test)]
+    fn sample() -> OpModus {
+        OpModus::from_raw(0xbb, 0x00)
+    }
+}
+
+/// A calendar date as the ACU keeps it, day, month and a two digit year.
+///
+/// So only 2000 to 2099 fit, anything else is refused on construction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ServiceDate {
+    // in this order, so the derived Ord is chronological.
+    year: u16,
+    month: u8,
+    day: u8,
+}
+
+// only asked about 2000 to 2099, where every fourth year is one.
+fn is_leap(year: u16) -> bool {
+    year & 3 == 0
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl ServiceDate {
+    pub fn new(day: u8, month: u8, year: u16) -> Result<ServiceDate, String> {
+        if !(2000..=2099).contains(&year) {
+            return Err(format!("year {} does not fit, the ACU keeps 2000 to 2099", year));
+        }
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+            return Err(format!("{:02}/{:02}/{} is not a date", day, month, year));
+        }
+        Ok(ServiceDate{ year, month, day })
+    }
+
+    /// The date `days` days after 1970-01-01, e.g. from `SystemTime`.
+    pub fn from_unix_days(days: i64) -> Result<ServiceDate, String> {
+        // civil_from_days() from Howard Hinnant's date algorithms.
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z - era * 146_097;
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+        if !(0..=u16::MAX as i64).contains(&year) {
+            return Err(format!("year {} does not fit, the ACU keeps 2000 to 2099", year));
+        }
+        ServiceDate::new(day as u8, month as u8, year as u16)
+    }
+
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+
+    pub fn month(&self) -> u8 {
+        self.month
+    }
+
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+
+    /// Whole months from `earlier` to this date, negative if it is later.
+    pub fn months_since(&self, earlier: &ServiceDate) -> i32 {
+        let months = |d: &ServiceDate| d.year as i32 * 12 + d.month as i32;
+        let n = months(self) - months(earlier);
+        if n > 0 && self.day < earlier.day {
+            n - 1
+        } else if n < 0 && self.day > earlier.day {
+            n + 1
+        } else {
+            n
+        }
+    }
+
+    /// The same day `n` months later, or the end of that month if it is
+    /// shorter. Fails past 2099.
+    pub fn add_months(&self, n: u32) -> Result<ServiceDate, String> {
+        // `n` comes from the user, so the months may not even fit a u32.
+        let m = (self.month as u32 - 1).checked_add(n);
+        match m.map(|m| (self.year as u32 + m / 12, (m % 12) as u8 + 1)) {
+            Some((year, month)) if year <= 2099 => {
+                let year = year as u16;
+                ServiceDate::new(self.day.min(days_in_month(year, month)), month, year)
+            },
+            Some((year, _)) => Err(format!("year {} does not fit, the ACU keeps 2000 to 2099", year)),
+            None => Err(format!("{} months on from {} does not fit, the ACU keeps 2000 to 2099", n, self)),
+        }
+    }
+}
+
+// the earliest date that fits.
+impl Default for ServiceDate {
+    fn default() -> ServiceDate {
+        ServiceDate{ year: 2000, month: 1, day: 1 }
+    }
+}
+
+impl fmt::Display for ServiceDate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02}/{:02}/{}", self.day, self.month, self.year)
+    }
+}
+
+/// ISO 8601, "yyyy-mm-dd".
+#[cfg(feature = "serde")]
+impl serde::Serialize for ServiceDate {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(&format_args!("{}-{:02}-{:02}", self.year, self.month, self.day))
+    }
+}
+
+/// "dd/mm/yyyy", as it is displayed.
+impl FromStr for ServiceDate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || format!("'{}' is not a date such as 15/03/2024", s);
+        let f: Vec<&str> = s.split('/').collect();
+        match f.as_slice() {
+            [d, m, y] if d.len() <= 2 && m.len() <= 2 && y.len() == 4 => {
+                ServiceDate::new(d.parse().map_err(|_| bad())?,
+                                 m.parse().map_err(|_| bad())?,
+                                 y.parse().map_err(|_| bad())?)
+            },
+            _ => Err(bad()),
+        }
+    }
+}
+
+// day || month || year - 2000 on the wire.
+pub(crate) struct Date;
+
+impl Field<ServiceDate> for Date {
+    fn get(p: &mut Payload) -> Result<ServiceDate, DecodeError> {
+        let d = p.bytes(3)?;
+        ServiceDate::new(d[0], d[1], 2000 + d[2] as u16).map_err(|why| {
+            debug!("service date {:02x?}: {}", d, why);
+            p.invalid()
+        })
+    }
+    fn put<W: Write>(d: &ServiceDate, w: &mut W) -> Result<(), WriteError> {
+        w.write_all(&[d.day, d.month, (d.year - 2000) as u8])
+    }
+    #[cfg(test)]
+    fn sample() -> ServiceDate {
+        ServiceDate::new(24, 6, 2020).unwrap()
+    }
+}
+
+/// `Date`, but a unit that was never serviced answers all 0x00 or all 0xff
+/// and that decodes to `None`.
+pub(crate) struct MaybeDate;
+
+impl Field<Option<ServiceDate>> for MaybeDate {
+    fn get(p: &mut Payload) -> Result<Option<ServiceDate>, DecodeError> {
+        let mut q = p.clone();
+        if let [0x00, 0x00, 0x00] | [0xff, 0xff, 0xff] = q.bytes(3)? {
+            *p = q;
+            return Ok(None);
+        }
+        Date::get(p).map(Some)
+    }
+    fn put<W: Write>(d: &Option<ServiceDate>, w: &mut W) -> Result<(), WriteError> {
+        match d {
+            Some(d) => Date::put(d, w),
+            None    => w.write_all(&[0x00, 0x00, 0x00]),
+        }
+    }
+    #[cfg(test)]
+    fn sample() -> Option<ServiceDate> {
+        Some(Date::sample())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    #[test]
+    fn op_modus_round_trips() {
+        for mode in 0..=255u8 {
+            for addons in 0..=255u8 {
+                assert_eq!(OpModus::from_raw(mode, addons).to_raw(), (mode, addons));
+            }
+        }
+        let m = OpModus{ mode: OpMode::RaceVestStreet, addons: OpAddons::GPS | OpAddons::OPEN_LOOP_OFF };
+        let mut buf = Vec::new();
+        Modus::put(&m, &mut buf).unwrap();
+        assert_eq!(buf, [0xcc, 0x18]);
+        let mut v = vec![0x03];
+        v.extend_from_slice(&buf);
+        assert_eq!(Modus::get(&mut Payload::new(0x00, &v).unwrap()), Ok(m));
+    }
+    #[test]
+    fn op_addons_decode() {
+        // used to compare `flags & 4 == 1`, which never held.
+        let m = OpModus::from_raw(0xbb, 0x0c);
+        assert_eq!(m.mode, OpMode::Race3S);
+        assert!(m.addons.contains(OpAddons::SD_CARD | OpAddons::GPS));
+        assert!(!m.addons.contains(OpAddons::OPEN_LOOP_OFF));
+        assert_eq!(m.addons.names().collect::<Vec<_>>(), ["sd_card", "gps"]);
+    }
+    #[test]
+    fn op_modus_display() {
+        assert_eq!(OpModus::from_raw(0xbb, 0x0c).to_string(), "RACE 3S, add-ons: SD card, GPS module");
+        assert_eq!(OpModus::from_raw(0xaa, 0x00).to_string(), "STREET, add-ons: none");
+        assert_eq!(OpModus::from_raw(0x12, 0x11).to_string(),
+                   "unknown mode 0x12, add-ons: open-loop detection off, unknown bits 0x01");
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn op_modus_json() {
+        let m = OpModus::from_raw(0xbb, 0x0c);
+        assert_eq!(serde_json::to_string(&m).unwrap(),
+                   r#"{"mode":"race_3s","addons":["sd_card","gps"],"mode_raw":187,"addons_raw":12}"#);
+        let m = OpModus::from_raw(0x12, 0x00);
+        assert_eq!(serde_json::to_string(&m).unwrap(),
+                   r#"{"mode":"unknown","addons":[],"mode_raw":18,"addons_raw":0}"#);
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn versions_and_dates_json() {
+        assert_eq!(serde_json::to_string(&HardwareVersion(305)).unwrap(), r#""3.05""#);
+        let d = ServiceDate::new(4, 6, 2020).unwrap();
+        assert_eq!(serde_json::to_string(&d).unwrap(), r#""2020-06-04""#);
+    }
+    #[test]
+    fn hardware_versions() {
+        assert_eq!("1".parse::<HardwareVersion>(), Ok(HardwareVersion(100)));
+        assert_eq!("1.5".parse::<HardwareVersion>(), Ok(HardwareVersion(150)));
+        assert_eq!("1.05".parse::<HardwareVersion>(), Ok(HardwareVersion(105)));
+        for bad in &["", "1.", ".5", "1.005", "-1", "1.x", "655.36"] {
+            assert!(bad.parse::<HardwareVersion>().is_err(), "{}", bad);
+        }
+        let v = HardwareVersion::from_f32(3.05);
+        assert_eq!((v.major(), v.minor()), (3, 5));
+        assert_eq!(v.to_string(), "3.05");
+        assert_eq!(v.raw(), 305);
+    }
+    #[test]
+    fn ctrl_modes() {
+        assert!("normal".parse::<CtrlMode>().is_err());
+        assert_eq!("0".parse::<CtrlMode>(), Ok(CtrlMode(0x00)));
+        assert_eq!("0x2a".parse::<CtrlMode>(), Ok(CtrlMode(0x2a)));
+        assert_eq!("42".parse::<CtrlMode>(), Ok(CtrlMode(0x2a)));
+        assert!("256".parse::<CtrlMode>().is_err());
+        assert_eq!(CtrlMode(0x00).to_string(), "0x00");
+        assert_eq!(CtrlMode(0x2a).to_string(), "0x2a");
+    }
+    #[test]
+    fn service_dates() {
+        let d = "15/03/2024".parse::<ServiceDate>().unwrap();
+        assert_eq!((d.day(), d.month(), d.year()), (15, 3, 2024));
+        assert_eq!(d.to_string(), "15/03/2024");
+        assert_eq!("5/3/2024".parse::<ServiceDate>(), ServiceDate::new(5, 3, 2024));
+        for bad in &["29/02/2023", "31/04/2024", "00/01/2024", "01/13/2024", "01/01/1999",
+                     "01/01/24", "01-01-2024", "1/1/2024/1", ""] {
+            assert!(bad.parse::<ServiceDate>().is_err(), "{}", bad);
+        }
+        assert!(ServiceDate::new(29, 2, 2024).is_ok());
+    }
+    #[test]
+    fn service_date_arithmetic() {
+        let d = |s: &str| s.parse::<ServiceDate>().unwrap();
+        assert_eq!(d("15/03/2026").months_since(&d("15/03/2024")), 24);
+        assert_eq!(d("14/03/2026").months_since(&d("15/03/2024")), 23);
+        assert_eq!(d("15/03/2024").months_since(&d("14/04/2024")), 0);
+        assert_eq!(d("15/03/2024").months_since(&d("16/04/2024")), -1);
+        assert_eq!(d("15/03/2024").months_since(&d("15/05/2024")), -2);
+        assert!(d("01/01/2025") > d("31/12/2024"));
+        assert_eq!(d("31/01/2024").add_months(1), Ok(d("29/02/2024")));
+        assert_eq!(d("15/11/2024").add_months(24), Ok(d("15/11/2026")));
+        assert!(d("01/06/2099").add_months(12).is_err());
+        assert!(d("15/12/2024").add_months(u32::MAX).is_err());
+    }
+    #[test]
+    fn dates_from_the_clock() {
+        assert_eq!(ServiceDate::from_unix_days(10_957), ServiceDate::new(1, 1, 2000));
+        assert_eq!(ServiceDate::from_unix_days(19_782), ServiceDate::new(29, 2, 2024));
+        assert!(ServiceDate::from_unix_days(0).is_err());
+    }
+    #[test]
+    fn blank_service_dates() {
+        for blank in &[[0x0a, 0x00, 0x00, 0x00], [0x0a, 0xff, 0xff, 0xff]] {
+            assert_eq!(MaybeDate::get(&mut Payload::new(0x00, blank).unwrap()), Ok(None));
+        }
+        let mut p = Payload::new(0x00, &[0x0a, 0x18, 0x06, 0x14]).unwrap();
+        assert_eq!(MaybeDate::get(&mut p), Ok(ServiceDate::new(24, 6, 2020).ok()));
+        assert!(MaybeDate::get(&mut Payload::new(0x00, &[0x0a, 0x00, 0x06, 0x14]).unwrap()).is_err());
+        let mut buf = Vec::new();
+        MaybeDate::put(&None, &mut buf).unwrap();
+        assert_eq!(buf, [0x00, 0x00, 0x00]);
+    }
+}
@@ -0,0 +1,52 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::cmd::codec::Field;
+use crate::cmd::payload::Payload;
+use crate::error::DecodeError;
+use crate::encoder::{invalid_value, Write, WriteError};
+
+
+// "hhh:mm:ss" on our side, hours(2) || mins || secs on the wire.
+pub(crate) struct Hours;
+
+impl Field<String> for Hours {
+    fn get(p: &mut Payload) -> Result<String, DecodeError> {
+        let hours = p.u16()?;
+        let (mins, secs) = (p.u8()?, p.u8()?);
+        Ok(format!("{:02}:{:02}:{:02}", hours, mins, secs))
+    }
+    fn put<W: Write>(h: &String, w: &mut W) -> Result<(), WriteError> {
+        let bad = || invalid_value("operating hours are not hhh:mm:ss");
+        let f = h.split(':').map(|f| f.parse::<u16>()).collect::<Result<Vec<_>, _>>().map_err(|_| bad())?;
+        match f[..] {
+            [hours, mins, secs] if mins < 60 && secs < 60 => {
+                w.write_all(&hours.to_be_bytes())?;
+                w.write_all(&[mins as u8, secs as u8])
+            },
+            _ => Err(bad()),
+        }
+    }
+    #[cfg(test)]
+    fn sample() -> String {
+        "123:45:06".into()
+    }
+}
@@ -49,6 +49,17 @@ impl From<CRC16> for u16 {
 	}
 }
 
+// f32::round() needs std, this rounds half away from zero the same way
+// for anything that fits a u16.
+pub fn round(v: f32) -> f32 {
+    let t = v as i32 as f32;
+    if v - t >= 0.5 {
+        t + 1.0
+    } else if v - t <= -0.5 {
+        t - 1.0
+    } else { t }
+}
+
 // 16bit fixed-point format: 13.3 (13 integral bits, 3 fractional bits).
 pub fn fixed16_to_double(v: u16) -> f32 {
     const FIXED_POINT_FRACTIONAL_BITS: u32 = 3;
@@ -56,6 +67,13 @@ pub fn fixed16_to_double(v: u16) -> f32 {
     return (v as f32) / b.pow(FIXED_POINT_FRACTIONAL_BITS) as f32;
 }
 
+// inverse of fixed16_to_double().
+pub fn double_to_fixed16(v: f32) -> u16 {
+    const FIXED_POINT_FRACTIONAL_BITS: u32 = 3;
+    let b: i32 = 10;
+    round(v * b.pow(FIXED_POINT_FRACTIONAL_BITS) as f32) as u16
+}
+
 pub fn calculate_accel(v: u16) -> f32 {
     return fixed16s_to_double(v) * (9984.0 / 625.0);
 }
@@ -64,6 +82,16 @@ pub fn calculate_gyro(v: u16) -> f32 {
     return fixed16s_to_double(v) * 2279.513043;
 }
 
+// inverse of calculate_accel().
+pub fn accel_to_raw(a: f32) -> u16 {
+    double_to_fixed16s(a / (9984.0 / 625.0))
+}
+
+// inverse of calculate_gyro().
+pub fn gyro_to_raw(g: f32) -> u16 {
+    double_to_fixed16s(g / 2279.513043)
+}
+
 // 16bit fixed-point (special - used base-2 here and sign bit).
 fn fixed16s_to_double(v: u16) -> f32 {
     let s = if 0x8000 & v == 1 { -1.0 } else { 1.0 };
@@ -73,6 +101,10 @@ fn fixed16s_to_double(v: u16) -> f32 {
     return d / (1 << 15) as f32;
 }
 
+fn double_to_fixed16s(d: f32) -> u16 {
+    round(d * (1 << 15) as f32) as u16
+}
+
 pub fn calculate_num_pages(hex_file_sz: usize) -> u16 {
     let page_count = if hex_file_sz % 256 == 0 {
         (hex_file_sz / 256) - 1
@@ -84,6 +116,15 @@ pub fn calculate_num_pages(hex_file_sz: usize) -> u16 {
 
 #[cfg(test)]
 mod tests {
+    use alloc::vec;
+
+    #[test]
+    fn round() {
+        assert_eq!(super::round(2.5), 3.0);
+        assert_eq!(super::round(2.49), 2.0);
+        assert_eq!(super::round(-2.5), -3.0);
+        assert_eq!(super::round(43707.0), 43707.0);
+    }
     #[test]
     fn crc16() {
         let v: u16 = 0xAABB;
@@ -119,6 +160,12 @@ mod tests {
         assert_eq!(d, 1.3338318);
     }
     #[test]
+    fn double_to_fixed16() {
+        assert_eq!(super::double_to_fixed16(43.707), 0xAABB);
+        assert_eq!(super::accel_to_raw(21.307161), 0xAABB);
+        assert_eq!(super::gyro_to_raw(3040.4868), 0xAABB);
+    }
+    #[test]
     fn calculate_num_pages() {
         let hfsz = 0x1080;
         let np = super::calculate_num_pages(hfsz);
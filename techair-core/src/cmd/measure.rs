@@ -0,0 +1,92 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+
+use crate::cmd::codec::Field;
+use crate::cmd::math::*;
+use crate::cmd::payload::Payload;
+use crate::error::DecodeError;
+use crate::encoder::{Write, WriteError};
+
+
+pub(crate) struct Voltage;
+
+impl Field<f32> for Voltage {
+    fn get(p: &mut Payload) -> Result<f32, DecodeError> {
+        Ok(fixed16_to_double(p.u16()?)) //* 10.0;
+    }
+    fn put<W: Write>(v: &f32, w: &mut W) -> Result<(), WriteError> {
+        w.write_all(&double_to_fixed16(*v).to_be_bytes())
+    }
+    #[cfg(test)]
+    fn sample() -> f32 {
+        3.3
+    }
+}
+
+pub(crate) struct Charging;
+
+impl Field<String> for Charging {
+    fn get(p: &mut Payload) -> Result<String, DecodeError> {
+        Ok(decode_charging_state(p.u8()?))
+    }
+    fn put<W: Write>(s: &String, w: &mut W) -> Result<(), WriteError> {
+        w.write_all(&[encode_charging_state(s)])
+    }
+    #[cfg(test)]
+    fn sample() -> String {
+        decode_charging_state(0x06)
+    }
+}
+
+//	MEASURE = 0x3,
+//	GET_CHARGING_STATE = 0x8,
+//	[0x03, 0x08, <CRC16>(.,.)]
+pub fn decode_charging_state(v: u8) -> String {
+    let s0 = if 1 & v > 0 {
+        "Over or undervoltage present, "
+    } else if 2 & v > 0 {
+            "USB Power OK, "
+    } else { "" };
+    let s1 = match v >> 2 {
+        0 => "Charge suspend",
+        1 => "Fast charge",
+        2 => "Charge done",
+        3 => "Pre charge",
+        _ => "Error in charge information"
+    };
+    return s0.to_owned() + s1;
+}
+
+// inverse of decode_charging_state().
+pub fn encode_charging_state(s: &str) -> u8 {
+    let (mut v, s1) = if let Some(r) = s.strip_prefix("Over or undervoltage present, ") {
+        (1, r)
+    } else if let Some(r) = s.strip_prefix("USB Power OK, ") {
+        (2, r)
+    } else { (0, s) };
+    v |= match s1 {
+        "Charge suspend" => 0,
+        "Fast charge"    => 1,
+        "Charge done"    => 2,
+        "Pre charge"     => 3,
+        _                => 4,
+    } << 2;
+    v
+}
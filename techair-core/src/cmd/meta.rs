@@ -0,0 +1,207 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+
+// What is known about each command, beyond how to encode it.
+//
+// One entry per (group, subcommand), so the framer, the timeouts and the
+// safety checks all look things up here rather than special casing
+// commands where they happen to be used. The entries come from the rows
+// of the protocol table, next to the codecs of each command.
+
+use core::fmt;
+use core::time::Duration;
+
+use crate::cmd::math::round;
+use crate::cmd::protocol::COMMANDS;
+#[cfg(feature = "alloc")]
+use crate::usbcmd::UsbCmd;
+
+
+/// How much payload follows the subcommand byte of a frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PayloadLen {
+    Fixed(usize),
+    // big-endian count of `width` bytes after `skip` header bytes,
+    // followed by that many bytes of data.
+    Counted { skip: usize, width: usize },
+    Unknown,
+}
+
+/// What sending a command can do to the unit, least harmful first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Safety {
+    /// Only reads state.
+    ReadOnly,
+    /// Changes settings that can be put back.
+    Config,
+    /// Loses data for good, e.g. clearing logs or flashing firmware.
+    Destructive,
+    /// Arms or fires the airbag.
+    Pyrotechnic,
+}
+
+impl fmt::Display for Safety {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Safety::ReadOnly    => "read-only",
+            Safety::Config      => "config",
+            Safety::Destructive => "destructive",
+            Safety::Pyrotechnic => "pyrotechnic",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CmdInfo {
+    pub group: u8,
+    pub sub: u8,
+    pub name: &'static str,
+    pub request: PayloadLen,
+    pub response: PayloadLen,
+    /// Longer than the session default, if the ACU needs it.
+    pub timeout: Option<Duration>,
+    pub safety: Safety,
+    /// Safe to resend when the reply got lost.
+    pub idempotent: bool,
+    /// Lowest ACU software version that knows it, see `firmware_number()`.
+    pub min_firmware: Option<u16>,
+}
+
+// the protocol table starts every row from one of these and fills in
+// group, subcommand and name with at().
+
+/// A command that changes the unit as much as `safety` says.
+pub(crate) const fn command(safety: Safety) -> CmdInfo {
+    CmdInfo{
+        group: 0,
+        sub: 0,
+        name: "",
+        request: PayloadLen::Fixed(0),
+        response: PayloadLen::Unknown,
+        timeout: None,
+        safety,
+        idempotent: matches!(safety, Safety::ReadOnly),
+        min_firmware: None,
+    }
+}
+
+/// A plain read answered with `response`.
+pub(crate) const fn read(response: PayloadLen) -> CmdInfo {
+    command(Safety::ReadOnly).with_response(response)
+}
+
+/// A read that steps through a log, each call returns the next part.
+pub(crate) const fn stream() -> CmdInfo {
+    CmdInfo{ idempotent: false, ..command(Safety::ReadOnly) }
+}
+
+impl CmdInfo {
+    pub(crate) const fn at(self, group: u8, sub: u8, name: &'static str) -> CmdInfo {
+        CmdInfo{ group, sub, name, ..self }
+    }
+    pub(crate) const fn with_request(self, request: PayloadLen) -> CmdInfo {
+        CmdInfo{ request, ..self }
+    }
+    pub(crate) const fn with_response(self, response: PayloadLen) -> CmdInfo {
+        CmdInfo{ response, ..self }
+    }
+    pub(crate) const fn with_timeout(self, timeout: Duration) -> CmdInfo {
+        CmdInfo{ timeout: Some(timeout), ..self }
+    }
+    pub(crate) const fn with_min_firmware(self, min_firmware: u16) -> CmdInfo {
+        CmdInfo{ min_firmware: Some(min_firmware), ..self }
+    }
+}
+
+/// Every known command, ordered by (group, subcommand).
+pub fn commands() -> &'static [CmdInfo] {
+    COMMANDS
+}
+
+pub fn lookup(group: u8, sub: u8) -> Option<&'static CmdInfo> {
+    COMMANDS.iter().find(|c| c.group == group && c.sub == sub)
+}
+
+/// The integer version the firmware compares against, e.g. 2.79 -> 279.
+pub fn firmware_number(sw_version: f32) -> u16 {
+    round(sw_version * 100.0) as u16
+}
+
+/// Per-command metadata, see `CmdInfo`.
+pub trait CommandMeta {
+    /// (group, subcommand) as they appear on the wire.
+    fn wire_ids(&self) -> (u8, u8);
+
+    fn info(&self) -> &'static CmdInfo {
+        let (group, sub) = self.wire_ids();
+        // every row of the protocol table has an entry, so this cannot miss.
+        lookup(group, sub).expect("command missing from the metadata table")
+    }
+    fn name(&self) -> &'static str {
+        self.info().name
+    }
+    fn response_len(&self) -> PayloadLen {
+        self.info().response
+    }
+    fn timeout(&self) -> Option<Duration> {
+        self.info().timeout
+    }
+    fn safety(&self) -> Safety {
+        self.info().safety
+    }
+    fn is_idempotent(&self) -> bool {
+        self.info().idempotent
+    }
+    fn min_firmware(&self) -> Option<u16> {
+        self.info().min_firmware
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl CommandMeta for UsbCmd {
+    fn wire_ids(&self) -> (u8, u8) {
+        self.ids()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::prelude::*;
+
+    #[test]
+    fn table_is_sorted_and_unique() {
+        for w in COMMANDS.windows(2) {
+            assert!((w[0].group, w[0].sub) < (w[1].group, w[1].sub), "{} / {}", w[0].name, w[1].name);
+        }
+    }
+    #[test]
+    fn commands_have_metadata() {
+        let fire = UsbCmd::Airbag(AirbagCmd::FireAIRBAG);
+        assert_eq!(fire.name(), "FireAIRBAG");
+        assert_eq!(fire.safety(), Safety::Pyrotechnic);
+        let th = UsbCmd::Algorithm(AlgorithmCmd::SetAlogrithmDefaultThresholds(None));
+        assert_eq!(th.min_firmware(), Some(279));
+        let crc = UsbCmd::SWUpdate(SWUpdateCmd::CRCCheck(0));
+        assert_eq!(crc.timeout(), Some(Duration::from_millis(3000)));
+        assert!(UsbCmd::General(GeneralCmd::GetSerialNr(None)).is_idempotent());
+        assert!(!UsbCmd::Logging(LoggingCmd::GetPreCrashBulk).is_idempotent());
+        assert!(Safety::ReadOnly < Safety::Destructive);
+        assert_eq!(firmware_number(2.79), 279);
+    }
+}
@@ -17,28 +17,42 @@
 pub mod prelude {
     pub use crate::cmd::math::{CRC16, calculate_num_pages};
 
-    pub use crate::cmd::general::GeneralCmd;
-    pub use crate::cmd::general::OpModus;
+    #[cfg(feature = "alloc")]
+    pub use crate::cmd::commands::*;
 
-    pub use crate::cmd::logging::LoggingCmd;
-    pub use crate::cmd::power::PowerCmd;
-    pub use crate::cmd::measure::MeasureCmd;
-    pub use crate::cmd::sensor::SensorCmd;
-    pub use crate::cmd::algorithm::AlgorithmCmd;
-    pub use crate::cmd::airbag::AirbagCmd;
+    pub use crate::cmd::meta::{CommandMeta, Safety};
+}
+
+// the command enums, they all need alloc.
+#[cfg(feature = "alloc")]
+mod commands {
+    pub use crate::cmd::protocol::{GeneralCmd, LoggingCmd, PowerCmd, MeasureCmd};
+    pub use crate::cmd::protocol::{SensorCmd, AlgorithmCmd, AirbagCmd, SWUpdateCmd};
 
-    pub use crate::cmd::swupdate::SWUpdateCmd;
+    pub use crate::cmd::general::{CtrlMode, HardwareVersion, OpModus, OpMode, OpAddons, ServiceDate, Setting};
+    pub use crate::cmd::measure::decode_charging_state;
     pub use crate::cmd::swupdate::SWUpdateBootLoaderStates;
     pub use crate::cmd::swupdate::FWData;
 }
 
-mod math;
+pub mod meta;
+pub mod math;
+
+#[cfg(feature = "alloc")]
+mod payload;
+#[cfg(feature = "alloc")]
+mod codec;
+pub(crate) mod protocol;
 
+#[cfg(feature = "alloc")]
 mod general;
+#[cfg(feature = "alloc")]
 mod logging;
-mod power;
+#[cfg(feature = "alloc")]
 mod measure;
+#[cfg(feature = "alloc")]
 mod sensor;
-mod algorithm;
+#[cfg(feature = "alloc")]
 mod airbag;
+#[cfg(feature = "alloc")]
 mod swupdate;
@@ -0,0 +1,123 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use crate::error::DecodeError;
+
+
+/// A bounds-checked cursor over `subcmd || payload`.
+///
+/// Every read checks there are enough bytes left and otherwise fails with
+/// `ShortPayload`, so a truncated or corrupt frame can never index past
+/// the end of the buffer.
+#[derive(Clone)]
+pub struct Payload<'a> {
+    group: u8,
+    sub: u8,
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Payload<'a> {
+    pub fn new(group: u8, v: &'a [u8]) -> Result<Payload<'a>, DecodeError> {
+        match v.split_first() {
+            Some((sub, data)) => Ok(Payload{ group, sub: *sub, data, pos: 0 }),
+            None => Err(DecodeError::MissingSubcommand(group)),
+        }
+    }
+
+    pub fn sub(&self) -> u8 {
+        self.sub
+    }
+
+    /// Bytes not yet read.
+    pub fn len(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Fails unless at least `n` more bytes are left.
+    pub fn need(&self, n: usize) -> Result<(), DecodeError> {
+        if self.len() < n {
+            return Err(DecodeError::ShortPayload{
+                group: self.group,
+                sub: self.sub,
+                needed: self.pos + n,
+                got: self.data.len(),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn bytes(&mut self, n: usize) -> Result<&'a [u8], DecodeError> {
+        self.need(n)?;
+        let b = &self.data[self.pos..(self.pos + n)];
+        self.pos += n;
+        Ok(b)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    // big-endian, as everything on the wire is.
+    pub fn u16(&mut self) -> Result<u16, DecodeError> {
+        let b = self.bytes(2)?;
+        Ok(((b[0] as u16) << 8) | b[1] as u16)
+    }
+
+    /// Everything left, possibly nothing.
+    pub fn rest(&mut self) -> &'a [u8] {
+        let b = &self.data[self.pos..];
+        self.pos = self.data.len();
+        b
+    }
+
+    pub fn unknown(&self) -> DecodeError {
+        DecodeError::UnknownSubcommand{ group: self.group, sub: self.sub }
+    }
+
+    pub fn invalid(&self) -> DecodeError {
+        DecodeError::InvalidPayload{ group: self.group, sub: self.sub }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_in_order() {
+        let v = [0x02, 0x01, 0x2c, 0x07];
+        let mut p = Payload::new(0x00, &v).unwrap();
+        assert_eq!(p.sub(), 0x02);
+        assert_eq!(p.u16().unwrap(), 300);
+        assert_eq!(p.u8().unwrap(), 7);
+        assert!(p.is_empty());
+    }
+    #[test]
+    fn short_reads_fail() {
+        let v = [0x07, 0x0f];
+        let mut p = Payload::new(0x03, &v).unwrap();
+        match p.u16() {
+            Err(DecodeError::ShortPayload{ group: 0x03, sub: 0x07, needed: 2, got: 1 }) => {},
+            r => panic!("{:?}", r),
+        }
+        assert!(matches!(Payload::new(0x03, &[]), Err(DecodeError::MissingSubcommand(0x03))));
+    }
+}
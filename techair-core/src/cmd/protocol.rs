@@ -0,0 +1,489 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+
+// The protocol table: every command the ACU knows, described once.
+//
+// Each row is `subcmd Name(Type): request, response => info;` where
+// request and response are the `Field` codecs used for the payload in that
+// direction, or just `subcmd Name => info;` when neither direction carries
+// one. `info` is the command's `CmdInfo`, built from the helpers in meta.rs.
+// protocol! turns the table into the group enums, UsbCmd, their encoders
+// and decoders and the metadata table, so adding or fixing a command only
+// ever touches its row. Only the metadata is built without alloc.
+
+use core::time::Duration;
+
+use crate::cmd::meta::{CmdInfo, command, read, stream};
+use crate::cmd::meta::PayloadLen::*;
+use crate::cmd::meta::Safety::*;
+
+#[cfg(feature = "alloc")]
+use core::convert::TryFrom;
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+#[cfg(feature = "alloc")]
+use crate::cmd::codec::*;
+#[cfg(feature = "alloc")]
+use crate::cmd::payload::Payload;
+#[cfg(feature = "alloc")]
+use crate::encoder::{CrcWriter, TechAirEncoder, TechAirResponseEncoder, Write, WriteError};
+#[cfg(feature = "alloc")]
+use crate::error::DecodeError;
+#[cfg(feature = "alloc")]
+use crate::usbcmd::strip_frame;
+
+#[cfg(feature = "alloc")]
+use crate::cmd::general::{Ack, CtrlMode, Date, HardwareVersion, HwVersion, MaybeDate, Mode, Modus, OpModus, Set,
+                          ServiceDate, Setting, Version};
+#[cfg(feature = "alloc")]
+use crate::cmd::logging::Hours;
+#[cfg(feature = "alloc")]
+use crate::cmd::measure::{Charging, Voltage};
+#[cfg(feature = "alloc")]
+use crate::cmd::sensor::{Accel, Gyro, Rev};
+#[cfg(feature = "alloc")]
+use crate::cmd::airbag::CalibRes;
+#[cfg(feature = "alloc")]
+use crate::cmd::swupdate::{BootState, FWData, FwChunk, FwPages, SWUpdateBootLoaderStates, Status};
+
+
+macro_rules! protocol {
+    // binds the field of a tuple variant to `$v`.
+    (@bind $v:ident $ty:ty) => { $v };
+
+    // the commands themselves carry strings and buffers.
+    (@alloc $( $item:item )*) => {
+        $( #[cfg(feature = "alloc")] $item )*
+    };
+
+    (@group $(#[$meta:meta])* $enum:ident = $gid:literal {
+        $( $sub:literal $name:ident $( ( $ty:ty ) : $req:ty , $resp:ty )? ; )*
+    }) => { protocol!{ @alloc
+        $(#[$meta])*
+        #[repr(u8)]
+        #[derive(Clone, Debug, PartialEq)]
+        pub enum $enum {
+            $( $name $( ($ty) )?, )*
+        }
+
+        impl $enum {
+            /// The group byte on the wire.
+            pub const GROUP: u8 = $gid;
+            /// (subcommand, name) of every command in the group.
+            pub const SUBCOMMANDS: &'static [(u8, &'static str)] = &[ $( ($sub, stringify!($name)), )* ];
+
+            // the subcommand byte on the wire.
+            pub fn id(&self) -> u8 {
+                match self {
+                    $( $enum::$name{ .. } => $sub, )*
+                }
+            }
+
+            // decodes a host -> ACU request, the reverse of encode().
+            pub fn decode_request(v: &[u8]) -> Result<Self, DecodeError> {
+                #[allow(unused_mut)]
+                let mut p = Payload::new($gid, v)?;
+                let cmd =
+                    match p.sub() {
+                        $( $sub => $enum::$name $( (<$req as Field<$ty>>::get(&mut p)?) )?, )*
+                        _ => return Err(p.unknown()),
+                    };
+                Ok(cmd)
+            }
+
+            // one of each command, requests with request fields and
+            // responses with response fields.
+            #[cfg(test)]
+            pub(crate) fn samples(response: bool) -> Vec<Self> {
+                let _ = response;
+                alloc::vec![ $( $enum::$name $( (
+                    if response { <$resp as Field<$ty>>::sample() } else { <$req as Field<$ty>>::sample() }
+                ) )?, )* ]
+            }
+        }
+
+        // decodes an ACU -> host response.
+        impl TryFrom<&[u8]> for $enum {
+            type Error = DecodeError;
+
+            fn try_from(v: &[u8]) -> Result<Self, Self::Error> {
+                #[allow(unused_mut)]
+                let mut p = Payload::new($gid, v)?;
+                let cmd =
+                    match p.sub() {
+                        $( $sub => $enum::$name $( (<$resp as Field<$ty>>::get(&mut p)?) )?, )*
+                        _ => return Err(p.unknown()),
+                    };
+                Ok(cmd)
+            }
+        }
+
+        impl TechAirEncoder for $enum {
+            fn encode<W: Write>(&self, w: &mut W) -> Result<(), WriteError> {
+                trace!("encode(): {}", stringify!($enum));
+                w.write_all(&[self.id()])?;
+                match self {
+                    $( $enum::$name $( (protocol!(@bind v $ty)) )? => {
+                        $( <$req as Field<$ty>>::put(v, w)?; )?
+                    }, )*
+                }
+                Ok(())
+            }
+        }
+
+        impl TechAirResponseEncoder for $enum {
+            fn encode_response<W: Write>(&self, w: &mut W) -> Result<(), WriteError> {
+                w.write_all(&[self.id()])?;
+                match self {
+                    $( $enum::$name $( (protocol!(@bind v $ty)) )? => {
+                        $( <$resp as Field<$ty>>::put(v, w)?; )?
+                    }, )*
+                }
+                Ok(())
+            }
+        }
+    } };
+
+    ($(
+        $(#[$meta:meta])*
+        $group:ident($enum:ident) = $gid:literal, $accessor:ident {
+            $( $sub:literal $name:ident $( ( $ty:ty ) : $req:ty , $resp:ty )? => $info:expr ; )*
+        }
+    )*) => {
+        $( protocol!(@group $(#[$meta])* $enum = $gid {
+            $( $sub $name $( ( $ty ) : $req , $resp )? ; )*
+        }); )*
+
+        /// Every command, as ordered in the table.
+        pub(crate) static COMMANDS: &[CmdInfo] = &[
+            $( $( $info.at($gid, $sub, stringify!($name)), )* )*
+        ];
+
+        protocol!{ @alloc
+        //
+        // NOTES on emum layout are defined here:
+        //   https://rust-lang.github.io/unsafe-code-guidelines/layout/enums.html
+        //
+        #[repr(u8)]
+        #[derive(Clone, Debug, PartialEq)]
+        pub enum UsbCmd {
+            $( $group($enum), )*
+        }
+
+        impl UsbCmd {
+            $(
+                pub fn $accessor(self) -> Option<$enum> {
+                    match self {
+                        UsbCmd::$group(cmd) => Some(cmd),
+                        _ => None,
+                    }
+                }
+            )*
+
+            /// Name of the group with id `group`, e.g. "Measure" for 0x03.
+            pub fn group_name(group: u8) -> Option<&'static str> {
+                match group {
+                    $( $gid => Some(stringify!($group)), )*
+                    _ => None,
+                }
+            }
+
+            // the group byte on the wire.
+            pub fn group_id(&self) -> u8 {
+                match self {
+                    $( UsbCmd::$group(_) => $gid, )*
+                }
+            }
+
+            /// (group, subcommand) as they appear on the wire.
+            pub fn ids(&self) -> (u8, u8) {
+                let sub = match self {
+                    $( UsbCmd::$group(cmd) => cmd.id(), )*
+                };
+                (self.group_id(), sub)
+            }
+
+            // decodes a host -> ACU request frame, the reverse of encode().
+            pub fn decode_request(v: &[u8]) -> Result<Self, DecodeError> {
+                let subdata = strip_frame(v)?;
+                let cmd =
+                    match v[0] {
+                        $( $gid => UsbCmd::$group($enum::decode_request(subdata)?), )*
+                        group => return Err(DecodeError::UnknownGroup(group)),
+                    };
+                Ok(cmd)
+            }
+        }
+
+        impl TechAirEncoder for UsbCmd {
+            fn encode<W: Write>(&self, w: &mut W) -> Result<(), WriteError> {
+                trace!("encode(): UsbCmd");
+                let mut w = CrcWriter::new(w);
+                w.write_all(&[self.group_id()])?;
+                match self {
+                    $( UsbCmd::$group(cmd) => cmd.encode(&mut w)?, )*
+                }
+                w.finish()
+            }
+        }
+
+        // encodes an ACU -> host response frame, the reverse of try_from().
+        impl TechAirResponseEncoder for UsbCmd {
+            fn encode_response<W: Write>(&self, w: &mut W) -> Result<(), WriteError> {
+                let mut w = CrcWriter::new(w);
+                w.write_all(&[self.group_id()])?;
+                match self {
+                    $( UsbCmd::$group(cmd) => cmd.encode_response(&mut w)?, )*
+                }
+                w.finish()
+            }
+        }
+
+        impl TryFrom<&[u8]> for UsbCmd {
+            type Error = DecodeError;
+
+            fn try_from(v: &[u8]) -> Result<Self, Self::Error> {
+                let subdata = strip_frame(v)?;
+                let cmd =
+                    match v[0] {
+                        $( $gid => UsbCmd::$group($enum::try_from(subdata)?), )*
+                        group => return Err(DecodeError::UnknownGroup(group)),
+                    };
+                Ok(cmd)
+            }
+        }
+
+        #[cfg(test)]
+        fn samples(response: bool) -> Vec<UsbCmd> {
+            let mut all = Vec::new();
+            $( all.extend($enum::samples(response).into_iter().map(UsbCmd::$group)); )*
+            all
+        }
+        }
+    };
+}
+
+protocol! {
+    General(GeneralCmd) = 0x00, general {
+        0x00 GetCtrlMode(Option<CtrlMode>): Absent, Opt<Mode> => read(Fixed(1));
+        // the setters answer with a status byte, non-zero when they did not take.
+        0x01 SetCtrlMode(Setting<CtrlMode>): Set<Mode>, Ack
+            => command(Config).with_request(Fixed(1)).with_response(Fixed(1));
+        // TODO: change variant inner type from Option<T> to just T.
+        0x02 GetSoftwareVersion(Option<f32>): Absent, Opt<Version> => read(Fixed(2));
+        0x03 GetOperatingModus(Option<OpModus>): Absent, OrNone<Modus> => read(Fixed(2));
+        0x04 GetSerialNr(Option<String>): Absent, Text => read(Unknown);
+        0x05 SetSerialNr(Setting<String>): Set<Text>, Ack
+            => command(Config).with_request(Unknown).with_response(Fixed(1));
+        0x06 GetHardwareVersion(Option<HardwareVersion>): Absent, OrNone<HwVersion> => read(Fixed(2));
+        0x07 SetHardwareVersion(Setting<HardwareVersion>): Set<HwVersion>, Ack
+            => command(Config).with_request(Fixed(2)).with_response(Fixed(1));
+        0x08 GetCustomerInfo(Option<String>): Absent, CountedText => read(Counted{ skip: 0, width: 1 });
+        0x09 SetCustomerInfo(Setting<String>): Set<CountedText>, Ack
+            => command(Config).with_request(Counted{ skip: 0, width: 1 }).with_response(Fixed(1));
+        // `None` in a reply means the unit was never serviced.
+        0x0a GetServiceDate(Option<ServiceDate>): Absent, MaybeDate => read(Fixed(3));
+        0x0b SetServiceDate(Setting<ServiceDate>): Set<Date>, Ack
+            => command(Config).with_request(Fixed(3)).with_response(Fixed(1));
+    }
+    Logging(LoggingCmd) = 0x01, logging {
+        0x00 GetOPHours(Option<String>): Absent, Opt<Hours> => read(Fixed(4));
+        0x01 ClearOPHours => command(Destructive);
+        0x02 GetNumOfErrors(Option<u8>): Absent, Opt<U8> => read(Fixed(1));
+        0x03 GetErrorEntry(Option<Vec<u8>>): Absent, Opt<Bytes> => read(Unknown);
+        0x04 ClearErrorHistory => command(Destructive);
+        0x05 GetNumOfPreCrashLogs(Option<u16>): Absent, Opt<U16> => read(Fixed(2)); // XXX correct decode?
+        0x06 GetPreCrashLogs(Option<Vec<u8>>): Absent, Opt<Bytes> => read(Unknown);
+        0x07 ClearPreCrashLog => command(Destructive);
+        0x08 GetNumOfPostCrashLogs(Option<u16>): Absent, Opt<U16> => read(Fixed(2)); // XXX correct decode?
+        0x09 GetPostCrashBulk(Option<u8>): Absent, OrNone<U8> => stream();
+        0x0a ClearPostCrashLog => command(Destructive);
+        0x0b GetPreCrashBulk => stream();
+        0x0c GetErrorHistory(Option<Vec<u8>>): Absent, Opt<Bytes> => read(Unknown);
+        0x0d GetPostCrashLogs(Option<Vec<u8>>): Absent, Opt<Bytes> => read(Unknown);
+        0x0e GetBatCount(Option<u16>): Absent, Opt<U16> => read(Fixed(2)); // XXX correct decode?
+        0x0f GetPreCrashENCBulk => stream();
+        0x10 GetPostCrashENCBulk => stream();
+    }
+    Power(PowerCmd) = 0x02, power {
+        0x00 GetSupplyState => read(Unknown);
+        0x01 ENDISSupply => command(Config);
+    }
+    Measure(MeasureCmd) = 0x03, measure {
+        0x00 GetLogicVoltage(f32): Absent, Voltage => read(Fixed(2));
+        0x01 GetPeripheralVoltage(f32): Absent, Voltage => read(Fixed(2));
+        0x02 GetRightHandVoltage(f32): Absent, Voltage => read(Fixed(2));
+        0x03 GetLeftHandVoltage(f32): Absent, Voltage => read(Fixed(2));
+        0x04 GetRightFootVoltage(f32): Absent, Voltage => read(Fixed(2));
+        0x05 GetLeftFootVoltage(f32): Absent, Voltage => read(Fixed(2));
+        0x06 GetSquibVoltage(f32): Absent, Voltage => read(Fixed(2));
+        0x07 GetBatteryVoltage(f32): Absent, Voltage => read(Fixed(2));
+        0x08 GetChargingState(Option<String>): Absent, Opt<Charging> => read(Fixed(1));
+        0x09 GetZIPSwitchState(bool): Absent, Bool => read(Fixed(1));
+        // USBSetLEDs(), nothing needed to be returned for a .SET. packet type?
+        0x0a SetEXTDisplay(u8): U8, Ignore => command(Config).with_request(Fixed(1));
+    }
+    Sensor(SensorCmd) = 0x04, sensor {
+        // the enable mask is optional.
+        0x00 EnableSensorReading(Option<u8>): OrNone<U8>, Absent => command(Config).with_request(Unknown);
+        0x01 GetSensorReadingEnables(u8): Absent, U8 => read(Fixed(1));
+        0x02 GetRightHandAccel(Option<(f32,f32,f32)>): Absent, Opt<Accel> => read(Fixed(6));
+        0x03 GetLeftHandAccel(Option<(f32,f32,f32)>): Absent, Opt<Accel> => read(Fixed(6));
+        0x04 GetRightFootAccel(Option<(f32,f32,f32)>): Absent, Opt<Accel> => read(Fixed(6));
+        0x05 GetLeftFootAccel(Option<(f32,f32,f32)>): Absent, Opt<Accel> => read(Fixed(6));
+        0x06 GetBodyAccel(Option<(f32,f32,f32)>): Absent, Opt<Accel> => read(Fixed(6));
+        0x07 GetGyroscope(Option<(f32,f32,f32)>): Absent, Opt<Gyro> => read(Fixed(6));
+        0x08 GetSWVRH((f32, f32)): Absent, Rev => read(Fixed(4));
+        0x09 GetSWVLH((f32, f32)): Absent, Rev => read(Fixed(4));
+        0x0a GetSWVRF((f32, f32)): Absent, Rev => read(Fixed(4));
+        0x0b GetSWVLF((f32, f32)): Absent, Rev => read(Fixed(4));
+    }
+    Algorithm(AlgorithmCmd) = 0x05, algorithm {
+        0x00 InitAlgorithm => command(Config);
+        0x01 DoSingleSampleCalc => command(Config);
+        0x02 GetSingleSampleCalcState => read(Unknown);
+        0x03 GetSingleSampleCalcResult => read(Unknown);
+        0x04 GetAlogrithmThresholds(Option<Vec<u8>>): Absent, Opt<Bytes> => read(Unknown);
+        0x05 SetAlogrithmThresholds => command(Config);
+        // needs uiSoftwareVersion >= 279, older firmware does not implement it.
+        0x06 SetAlogrithmDefaultThresholds(Option<u8>): Absent, Opt<U8>
+            => command(Config).with_response(Fixed(1)).with_min_firmware(279);
+    }
+    Airbag(AirbagCmd) = 0x06, airbag {
+        0x00 GetIgnitionCtrlMode => read(Unknown);
+        0x01 GetIgnitionCtrlStatus => read(Unknown);
+        0x02 InitIgnitionCtrl => command(Config);
+        0x03 DiagIgnitionCtrl => command(Config);
+        0x04 ArmIgnitionCtrl => command(Pyrotechnic);
+        0x05 FireAIRBAG => command(Pyrotechnic); // Umm !? refused unless the safety limit allows it.
+        0x06 ResetIgnitionCtrl => command(Config);
+        0x07 DiagGetSquibRes => command(Config);
+        0x08 GetCalibSquibRes((Option<f32>, Option<f32>)): Absent, CalibRes => read(Fixed(4));
+        0x09 SetCalibSquibRes => command(Config);
+        0x0a GetInflationType(u8): Absent, U8 => read(Fixed(1));
+        0x0b SetInflationType(u8): U8, Absent => command(Config).with_request(Fixed(1));
+    }
+    // SDCard,
+    // Configure, // 0x10
+    SWUpdate(SWUpdateCmd) = 0x20, swupdate {
+        0x00 StartBootLoader => command(Destructive);
+        0x01 GetBootLoaderVersion(Option<u8>): Absent, Opt<U8> => read(Fixed(1));
+        0x02 GetBootLoaderState(Option<SWUpdateBootLoaderStates>): Absent, Opt<BootState> => read(Fixed(1));
+        0x03 WriteFWData(FWData): FwChunk, FwPages
+            => command(Destructive).with_request(Counted{ skip: 2, width: 2 }).with_response(Fixed(2));
+        0x04 QuitBootLoader => command(Destructive);
+        // the ACU checks the whole flashed image before answering.
+        0x05 CRCCheck(u16): U16, Status
+            => command(Destructive).with_request(Fixed(2)).with_response(Fixed(1))
+               .with_timeout(Duration::from_millis(3000));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+    use alloc::string::String;
+    use crate::cmd::meta::{PayloadLen, commands, lookup};
+
+    fn hex(v: &[u8]) -> String {
+        v.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" ")
+    }
+
+    fn frames(response: bool) -> Vec<(UsbCmd, Vec<u8>)> {
+        samples(response).into_iter().map(|cmd| {
+            let mut buf = Vec::new();
+            if response {
+                cmd.write_response_bytes(&mut buf);
+            } else {
+                cmd.write_bytes(&mut buf);
+            }
+            (cmd, buf)
+        }).collect()
+    }
+
+    // fails unless the payload of `frame` fits what the metadata table says.
+    fn check_len(name: &str, len: PayloadLen, frame: &[u8]) {
+        let payload = &frame[2..frame.len() - 2];
+        match len {
+            PayloadLen::Fixed(n) => assert_eq!(payload.len(), n, "{}", name),
+            PayloadLen::Counted{ skip, width } => {
+                let count = payload[skip..skip + width].iter().fold(0, |n, b| (n << 8) | *b as usize);
+                assert_eq!(payload.len(), skip + width + count, "{}", name);
+            },
+            PayloadLen::Unknown => {},
+        }
+    }
+
+    #[test]
+    fn golden_frames() {
+        let mut out = String::new();
+        for ((cmd, req), (_, resp)) in frames(false).into_iter().zip(frames(true)) {
+            let (group, sub) = cmd.ids();
+            out += &format!("{:02x}/{:02x} {}\n  > {}\n  < {}\n",
+                            group, sub, lookup(group, sub).unwrap().name, hex(&req), hex(&resp));
+        }
+        assert_eq!(out, include_str!("golden.txt"), "\n{}", out);
+    }
+    #[test]
+    fn samples_round_trip() {
+        for (cmd, frame) in frames(false) {
+            let back = UsbCmd::decode_request(&frame).unwrap();
+            assert_eq!(back.ids(), cmd.ids());
+            let mut again = Vec::new();
+            back.write_bytes(&mut again);
+            assert_eq!(again, frame, "{:?}", cmd);
+        }
+        for (cmd, frame) in frames(true) {
+            let back = UsbCmd::try_from(frame.as_slice()).unwrap();
+            assert_eq!(back.ids(), cmd.ids());
+            let mut again = Vec::new();
+            back.write_response_bytes(&mut again);
+            assert_eq!(again, frame, "{:?}", cmd);
+        }
+    }
+    #[test]
+    fn table_matches_metadata() {
+        let groups = [(GeneralCmd::GROUP, GeneralCmd::SUBCOMMANDS), (LoggingCmd::GROUP, LoggingCmd::SUBCOMMANDS),
+                      (PowerCmd::GROUP, PowerCmd::SUBCOMMANDS), (MeasureCmd::GROUP, MeasureCmd::SUBCOMMANDS),
+                      (SensorCmd::GROUP, SensorCmd::SUBCOMMANDS), (AlgorithmCmd::GROUP, AlgorithmCmd::SUBCOMMANDS),
+                      (AirbagCmd::GROUP, AirbagCmd::SUBCOMMANDS), (SWUpdateCmd::GROUP, SWUpdateCmd::SUBCOMMANDS)];
+        let mut n = 0;
+        for (group, subs) in groups.iter() {
+            for (sub, name) in subs.iter() {
+                let info = lookup(*group, *sub).expect("command missing from the metadata table");
+                assert_eq!(info.name, *name);
+                n += 1;
+            }
+        }
+        assert_eq!(n, commands().len());
+        assert_eq!(UsbCmd::group_name(0x20), Some("SWUpdate"));
+        assert_eq!(UsbCmd::group_name(0x10), None);
+
+        for (_, frame) in frames(false) {
+            let info = lookup(frame[0], frame[1]).unwrap();
+            check_len(info.name, info.request, &frame);
+        }
+        for (_, frame) in frames(true) {
+            let info = lookup(frame[0], frame[1]).unwrap();
+            check_len(info.name, info.response, &frame);
+        }
+    }
+}
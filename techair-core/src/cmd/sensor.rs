@@ -0,0 +1,102 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+
+use crate::cmd::codec::Field;
+use crate::cmd::math::*;
+use crate::cmd::payload::Payload;
+use crate::error::DecodeError;
+use crate::encoder::{Write, WriteError};
+
+
+fn decode_rev(p: &mut Payload) -> Result<(f32, f32), DecodeError> {
+    let s = p.u16()?;
+    let h = p.u16()?;
+    let sw = fixed16_to_double(s) * 10.0;
+    let hw = fixed16_to_double(h) * 10.0;
+    Ok((sw, hw))
+}
+
+fn decode_xyz(p: &mut Payload) -> Result<(u16, u16, u16), DecodeError> {
+    Ok((p.u16()?, p.u16()?, p.u16()?))
+}
+
+fn decode_accel(p: &mut Payload) -> Result<(f32, f32, f32), DecodeError> {
+    let (x, y, z) = decode_xyz(p)?;
+    Ok((calculate_accel(x), calculate_accel(y), calculate_accel(z)))
+}
+
+fn decode_gyro(p: &mut Payload) -> Result<(f32, f32, f32), DecodeError> {
+    let (x, y, z) = decode_xyz(p)?;
+    Ok((calculate_gyro(x), calculate_gyro(y), calculate_gyro(z)))
+}
+
+fn encode_xyz<W: Write>(w: &mut W, xyz: (u16, u16, u16)) -> Result<(), WriteError> {
+    w.write_all(&xyz.0.to_be_bytes())?;
+    w.write_all(&xyz.1.to_be_bytes())?;
+    w.write_all(&xyz.2.to_be_bytes())
+}
+
+fn encode_rev<W: Write>(w: &mut W, rev: &(f32, f32)) -> Result<(), WriteError> {
+    w.write_all(&double_to_fixed16(rev.0 / 10.0).to_be_bytes())?;
+    w.write_all(&double_to_fixed16(rev.1 / 10.0).to_be_bytes())
+}
+
+pub(crate) struct Accel;
+
+impl Field<(f32, f32, f32)> for Accel {
+    fn get(p: &mut Payload) -> Result<(f32, f32, f32), DecodeError> {
+        decode_accel(p)
+    }
+    fn put<W: Write>(a: &(f32, f32, f32), w: &mut W) -> Result<(), WriteError> {
+        encode_xyz(w, (accel_to_raw(a.0), accel_to_raw(a.1), accel_to_raw(a.2)))
+    }
+    #[cfg(test)]
+    fn sample() -> (f32, f32, f32) {
+        (0.5, 1.0, 2.0)
+    }
+}
+
+pub(crate) struct Gyro;
+
+impl Field<(f32, f32, f32)> for Gyro {
+    fn get(p: &mut Payload) -> Result<(f32, f32, f32), DecodeError> {
+        decode_gyro(p)
+    }
+    fn put<W: Write>(g: &(f32, f32, f32), w: &mut W) -> Result<(), WriteError> {
+        encode_xyz(w, (gyro_to_raw(g.0), gyro_to_raw(g.1), gyro_to_raw(g.2)))
+    }
+    #[cfg(test)]
+    fn sample() -> (f32, f32, f32) {
+        (10.0, 20.0, 30.0)
+    }
+}
+
+// (software, hardware) revision of a sensor.
+pub(crate) struct Rev;
+
+impl Field<(f32, f32)> for Rev {
+    fn get(p: &mut Payload) -> Result<(f32, f32), DecodeError> {
+        decode_rev(p)
+    }
+    fn put<W: Write>(rev: &(f32, f32), w: &mut W) -> Result<(), WriteError> {
+        encode_rev(w, rev)
+    }
+    #[cfg(test)]
+    fn sample() -> (f32, f32) {
+        (1.2, 3.4)
+    }
+}
@@ -0,0 +1,179 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+
+use core::convert::TryFrom;
+use alloc::borrow::ToOwned;
+use alloc::vec::Vec;
+
+use crate::cmd::codec::Field;
+use crate::cmd::payload::Payload;
+use crate::error::DecodeError;
+use crate::encoder::{Write, WriteError};
+
+
+#[repr(u8)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum SWUpdateBootLoaderStates {
+    WaitFW,
+    CheckReadFW,
+    EraseFlash,
+    FlashFW,
+    CalcFlashedCRC,
+    WaitVerifyFlashedCRC,
+    VerifyFlashedCRCMemory,
+}
+
+impl TryFrom<u8> for SWUpdateBootLoaderStates {
+    type Error = u8;
+
+    fn try_from(b: u8) -> Result<Self, Self::Error> {
+        let state =
+            match b {
+                0x0 => SWUpdateBootLoaderStates::WaitFW,
+                0x1 => SWUpdateBootLoaderStates::CheckReadFW,
+                0x2 => SWUpdateBootLoaderStates::EraseFlash,
+                0x3 => SWUpdateBootLoaderStates::FlashFW,
+                0x4 => SWUpdateBootLoaderStates::CalcFlashedCRC,
+                0x5 => SWUpdateBootLoaderStates::WaitVerifyFlashedCRC,
+                0x6 => SWUpdateBootLoaderStates::VerifyFlashedCRCMemory,
+                _ => return Err(b),
+            };
+        Ok(state)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FWData {
+    pub page_count: u16,
+    chunk_len: usize, // decryption_len - info_len (num2)
+    data: Option<Vec::<u8>>,
+    //crc: u16,//[u8;2],
+}
+
+impl FWData {
+    pub fn new(data: &[u8], page_count: &u16) -> Option<FWData> {
+        let chunk_len = data.len();
+        if chunk_len > 256 || chunk_len == 0 {
+            return None;
+        }
+        Some(FWData{
+            page_count: *page_count,
+            chunk_len: chunk_len,
+            data: Some(data.to_owned()),
+        })
+    }
+}
+
+impl FWData {
+    pub fn chunk_len(&self) -> usize {
+        self.chunk_len
+    }
+    pub fn data(&self) -> Option<&[u8]> {
+        self.data.as_deref()
+    }
+}
+
+pub(crate) struct BootState;
+
+impl Field<SWUpdateBootLoaderStates> for BootState {
+    fn get(p: &mut Payload) -> Result<SWUpdateBootLoaderStates, DecodeError> {
+        SWUpdateBootLoaderStates::try_from(p.u8()?).map_err(|_| p.invalid())
+    }
+    fn put<W: Write>(s: &SWUpdateBootLoaderStates, w: &mut W) -> Result<(), WriteError> {
+        w.write_all(&[s.clone() as u8])
+    }
+    #[cfg(test)]
+    fn sample() -> SWUpdateBootLoaderStates {
+        SWUpdateBootLoaderStates::WaitVerifyFlashedCRC
+    }
+}
+
+// page_count(2) || chunk_len(2) || chunk
+pub(crate) struct FwChunk;
+
+impl Field<FWData> for FwChunk {
+    fn get(p: &mut Payload) -> Result<FWData, DecodeError> {
+        let page_count = p.u16()?;
+        let chunk_len = p.u16()? as usize;
+        let chunk = p.bytes(chunk_len)?;
+        if !p.is_empty() {
+            return Err(p.invalid());
+        }
+        Ok(FWData{
+            page_count,
+            chunk_len,
+            data: Some(chunk.to_vec()),
+        })
+    }
+    fn put<W: Write>(fw_data: &FWData, w: &mut W) -> Result<(), WriteError> {
+        w.write_all(&fw_data.page_count.to_be_bytes())?;
+        w.write_all(&(fw_data.chunk_len as u16).to_be_bytes())?;
+        // buf[idx=6] = <data segment[0]> || crc16
+        // buf[idx=7] = <data segment[1.> || crc16
+        if let Some(data) = &fw_data.data {
+            w.write_all(data)?;
+        }
+        Ok(())
+    }
+    #[cfg(test)]
+    fn sample() -> FWData {
+        FWData::new(&[0x5a; 16], &3).unwrap()
+    }
+}
+
+// the pages still to come, 0xffff when the ACU did not take the chunk.
+pub(crate) struct FwPages;
+
+impl Field<FWData> for FwPages {
+    fn get(p: &mut Payload) -> Result<FWData, DecodeError> {
+        let page_count = p.u16()?;
+        if page_count == 0xFFFF {
+            debug!("err xfer hex data failed");
+            return Err(DecodeError::Firmware("xfer hex data failed"));
+        }
+        // if returned page_count != num of bytes sent, then fail!.
+        // if returned page_count == 0, then success!
+        Ok(FWData{
+            page_count,
+            chunk_len: 0,
+            data: None,
+        })
+    }
+    fn put<W: Write>(fw_data: &FWData, w: &mut W) -> Result<(), WriteError> {
+        w.write_all(&fw_data.page_count.to_be_bytes())
+    }
+    #[cfg(test)]
+    fn sample() -> FWData {
+        FWData{ page_count: 2, chunk_len: 0, data: None }
+    }
+}
+
+// non-zero value indicates a failed xfer
+pub(crate) struct Status;
+
+impl Field<u16> for Status {
+    fn get(p: &mut Payload) -> Result<u16, DecodeError> {
+        Ok(p.u8()? as u16)
+    }
+    fn put<W: Write>(ret: &u16, w: &mut W) -> Result<(), WriteError> {
+        w.write_all(&[*ret as u8])
+    }
+    #[cfg(test)]
+    fn sample() -> u16 {
+        1
+    }
+}
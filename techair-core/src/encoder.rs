@@ -0,0 +1,169 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use crc16::{State, MODBUS};
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+
+/// Where encoded frames go.
+///
+/// With the std feature this is anything `std::io::Write`, without it a
+/// `&mut [u8]` or, with alloc, a `Vec<u8>`.
+pub trait Write {
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), WriteError>;
+}
+
+#[cfg(feature = "std")]
+pub type WriteError = std::io::Error;
+
+/// There was no room left in the buffer.
+#[cfg(not(feature = "std"))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WriteError;
+
+// a value the wire format cannot carry.
+#[cfg(feature = "std")]
+pub(crate) fn invalid_value(why: &'static str) -> WriteError {
+    WriteError::new(std::io::ErrorKind::InvalidInput, why)
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn invalid_value(_why: &'static str) -> WriteError {
+    WriteError
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write + ?Sized> Write for W {
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), WriteError> {
+        std::io::Write::write_all(self, buf)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl Write for &mut [u8] {
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), WriteError> {
+        if buf.len() > self.len() {
+            return Err(WriteError);
+        }
+        let (head, tail) = core::mem::take(self).split_at_mut(buf.len());
+        head.copy_from_slice(buf);
+        *self = tail;
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+impl Write for Vec<u8> {
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), WriteError> {
+        self.extend_from_slice(buf);
+        Ok(())
+    }
+}
+
+/// The host side of the codec: encodes a request to the ACU.
+///
+/// `UsbCmd` writes the whole frame, group byte and CRC16 included, the
+/// group enums only their subcommand byte + payload.
+pub trait TechAirEncoder {
+    fn encode<W: Write>(&self, w: &mut W) -> Result<(), WriteError>;
+
+    #[cfg(feature = "alloc")]
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        // a Vec grows as needed, writing to one cannot fail.
+        self.encode(buf).expect("encoding into a Vec")
+    }
+
+    /// Encodes into `buf` and returns the length used, fails when it
+    /// does not fit.
+    fn encode_to_slice(&self, buf: &mut [u8]) -> Result<usize, WriteError> {
+        let len = buf.len();
+        let mut rest = buf;
+        self.encode(&mut rest)?;
+        Ok(len - rest.len())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl TechAirEncoder for Vec<u8> {
+    fn encode<W: Write>(&self, w: &mut W) -> Result<(), WriteError> {
+        w.write_all(self)
+    }
+}
+
+// The device side of the codec: encodes the ACU's reply to a command as
+// subcommand byte + payload, the group byte and CRC16 are added by UsbCmd.
+pub trait TechAirResponseEncoder {
+    fn encode_response<W: Write>(&self, w: &mut W) -> Result<(), WriteError>;
+
+    #[cfg(feature = "alloc")]
+    fn write_response_bytes(&self, buf: &mut Vec<u8>) {
+        self.encode_response(buf).expect("encoding into a Vec")
+    }
+
+    fn encode_response_to_slice(&self, buf: &mut [u8]) -> Result<usize, WriteError> {
+        let len = buf.len();
+        let mut rest = buf;
+        self.encode_response(&mut rest)?;
+        Ok(len - rest.len())
+    }
+}
+
+// Passes everything through to `inner`, keeping the CRC16 of it so the
+// frame trailer can be written without buffering the frame.
+pub struct CrcWriter<'a, W> {
+    inner: &'a mut W,
+    crc: State<MODBUS>,
+}
+
+impl<'a, W: Write> CrcWriter<'a, W> {
+    pub fn new(inner: &'a mut W) -> CrcWriter<'a, W> {
+        CrcWriter{ inner, crc: State::<MODBUS>::new() }
+    }
+
+    /// Appends the CRC16 of everything written so far, LSB first.
+    pub fn finish(self) -> Result<(), WriteError> {
+        let crc = self.crc.get();
+        self.inner.write_all(&crc.to_le_bytes())
+    }
+}
+
+impl<W: Write> Write for CrcWriter<'_, W> {
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), WriteError> {
+        self.inner.write_all(buf)?;
+        self.crc.update(buf);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::prelude::*;
+
+    #[test]
+    fn crc_writer_matches_crc16() {
+        let mut buf = [0u8; 4];
+        let mut out = &mut buf[..];
+        let mut w = CrcWriter::new(&mut out);
+        w.write_all(&[0x00, 0x04]).unwrap();
+        w.finish().unwrap();
+        assert_eq!(buf, [0x00, 0x04, 0x00, 0x73]);
+        let crc = CRC16::calculate(&buf[..2]).as_u16();
+        assert_eq!(buf[2..], crc.to_le_bytes());
+    }
+}
@@ -0,0 +1,101 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+
+use core::fmt;
+
+
+/// A frame that could not be decoded.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DecodeError {
+    CrcMismatch{ expected: u16, got: u16 },
+    Truncated{ needed: usize, got: usize },
+    UnknownGroup(u8),
+    MissingSubcommand(u8),
+    UnknownSubcommand{ group: u8, sub: u8 },
+    ShortPayload{ group: u8, sub: u8, needed: usize, got: usize },
+    InvalidPayload{ group: u8, sub: u8 },
+    // the bootloader answered, but to say the update failed.
+    Firmware(&'static str),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::CrcMismatch{ expected, got } =>
+                write!(f, "CRC16 mismatch, expected {:#06x} got {:#06x}", expected, got),
+            DecodeError::Truncated{ needed, got } =>
+                write!(f, "truncated frame, needed {} bytes got {}", needed, got),
+            DecodeError::UnknownGroup(group) => write!(f, "unknown command group {:#04x}", group),
+            DecodeError::MissingSubcommand(group) =>
+                write!(f, "no subcommand byte for group {:#04x}", group),
+            DecodeError::UnknownSubcommand{ group, sub } =>
+                write!(f, "unknown subcommand {:#04x} in group {:#04x}", sub, group),
+            DecodeError::ShortPayload{ group, sub, needed, got } =>
+                write!(f, "short payload for {:#04x}/{:#04x}, needed {} bytes got {}",
+                       group, sub, needed, got),
+            DecodeError::InvalidPayload{ group, sub } =>
+                write!(f, "could not decode the payload of {:#04x}/{:#04x}", group, sub),
+            DecodeError::Firmware(why) => write!(f, "firmware update failed: {}", why),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use core::convert::TryFrom;
+    use crate::cmd::prelude::*;
+    use crate::usbcmd::UsbCmd;
+
+    fn with_crc(mut v: Vec<u8>) -> Vec<u8> {
+        let crc = CRC16::calculate(&v).as_u16();
+        v.push((crc & 0xff) as u8);
+        v.push((crc >>   8) as u8);
+        v
+    }
+
+    #[test]
+    fn crc_mismatch() {
+        match UsbCmd::try_from(vec![0x00,0x02,0x01,0x17,0x00,0x00]) {
+            Err(DecodeError::CrcMismatch{ got: 0, .. }) => {},
+            r => panic!("{:?}", r),
+        }
+    }
+    #[test]
+    fn unknown_ids() {
+        match UsbCmd::try_from(with_crc(vec![0x42,0x00])) {
+            Err(DecodeError::UnknownGroup(0x42)) => {},
+            r => panic!("{:?}", r),
+        }
+        match UsbCmd::try_from(with_crc(vec![0x01,0x7f])) {
+            Err(DecodeError::UnknownSubcommand{ group: 0x01, sub: 0x7f }) => {},
+            r => panic!("{:?}", r),
+        }
+    }
+    #[test]
+    fn short_payload() {
+        match UsbCmd::try_from(with_crc(vec![0x03,0x07,0x0f])) {
+            Err(DecodeError::ShortPayload{ group: 0x03, sub: 0x07, needed: 2, got: 1 }) => {},
+            r => panic!("{:?}", r),
+        }
+    }
+}
@@ -0,0 +1,115 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+
+// A UsbCmd together with the way it travels.
+//
+// The same command is different bytes depending on who sends it: the host
+// sends the arguments, the ACU answers with the result. Frame picks the
+// matching half of the codec, so whatever sees both sides of the link
+// (the simulator, captures, a sniffer) does not have to.
+
+#[cfg(feature = "alloc")]
+use core::convert::TryFrom;
+#[cfg(feature = "alloc")]
+use crate::encoder::{TechAirEncoder, TechAirResponseEncoder, Write, WriteError};
+#[cfg(feature = "alloc")]
+use crate::error::DecodeError;
+#[cfg(feature = "alloc")]
+use crate::usbcmd::UsbCmd;
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Request,  // host -> ACU
+    Response, // ACU -> host
+}
+
+impl Direction {
+    /// The way the answer to a frame going this way travels.
+    pub fn reply(self) -> Direction {
+        match self {
+            Direction::Request  => Direction::Response,
+            Direction::Response => Direction::Request,
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub dir: Direction,
+    pub cmd: UsbCmd,
+}
+
+#[cfg(feature = "alloc")]
+impl Frame {
+    pub fn request(cmd: UsbCmd) -> Frame {
+        Frame{ dir: Direction::Request, cmd }
+    }
+
+    pub fn response(cmd: UsbCmd) -> Frame {
+        Frame{ dir: Direction::Response, cmd }
+    }
+
+    /// Decodes a whole frame, CRC16 included, as sent in `dir`.
+    pub fn decode(dir: Direction, v: &[u8]) -> Result<Frame, DecodeError> {
+        let cmd = match dir {
+            Direction::Request  => UsbCmd::decode_request(v)?,
+            Direction::Response => UsbCmd::try_from(v)?,
+        };
+        Ok(Frame{ dir, cmd })
+    }
+
+    /// (group, subcommand) as they appear on the wire.
+    pub fn ids(&self) -> (u8, u8) {
+        self.cmd.ids()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl TechAirEncoder for Frame {
+    fn encode<W: Write>(&self, w: &mut W) -> Result<(), WriteError> {
+        match self.dir {
+            Direction::Request  => self.cmd.encode(w),
+            Direction::Response => self.cmd.encode_response(w),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use crate::cmd::prelude::*;
+
+    #[test]
+    fn same_command_both_ways() {
+        let req = Frame::request(UsbCmd::SWUpdate(SWUpdateCmd::CRCCheck(0xaabb)));
+        let resp = Frame::response(UsbCmd::SWUpdate(SWUpdateCmd::CRCCheck(0x01)));
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        req.write_bytes(&mut a);
+        resp.write_bytes(&mut b);
+        assert_eq!(a, [0x20, 0x05, 0xaa, 0xbb, 0x25, 0x36]);
+        assert_eq!(b.len(), 5);
+        assert_eq!(Frame::decode(Direction::Request, &a).unwrap(), req);
+        assert_eq!(Frame::decode(Direction::Response, &b).unwrap(), resp);
+        // a request read as a response is just the wrong payload.
+        assert_eq!(Frame::decode(Direction::Response, &a).unwrap().cmd,
+                   UsbCmd::SWUpdate(SWUpdateCmd::CRCCheck(0xaa)));
+        assert_eq!(req.dir.reply(), Direction::Response);
+    }
+}
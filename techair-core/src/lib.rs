@@ -14,17 +14,34 @@
 // along with this program; if not, write to the Free Software
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
 
-// TODO: make parameteric over a Writer
-//use std::io::prelude::*;
-//use std::io::Write;
-pub trait TechAirEncoder {
-//pub trait TechAirEncoder<W: Write> {
-    fn write_bytes(&self, buf: &mut Vec<u8>);
-//    fn write_bytes(&self, uart: &mut W) -> std::io::Result<()>;
-}
-
-impl TechAirEncoder for Vec<u8> {
-    fn write_bytes(&self, buf: &mut Vec<u8>) {
-        buf.extend_from_slice(self.as_slice());
-    }
-}
+
+// The wire protocol of the ACU without any I/O: the UsbCmd codec, CRC16
+// and fixed-point helpers. Builds without std, so it can also run on a
+// microcontroller sitting on the link.
+
+#![no_std]
+
+#[cfg(feature = "std")]
+extern crate std;
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg_attr(feature = "alloc", macro_use)]
+extern crate log;
+
+pub mod error;
+pub mod encoder;
+pub mod frame;
+#[cfg(feature = "alloc")]
+pub mod usbcmd;
+
+// export the pub interface to cmd/mod.rs
+pub mod cmd;
+
+pub use crate::cmd::prelude::*;
+pub use crate::error::DecodeError;
+pub use crate::frame::Direction;
+#[cfg(feature = "alloc")]
+pub use crate::frame::Frame;
+#[cfg(feature = "alloc")]
+pub use crate::usbcmd::UsbCmd;
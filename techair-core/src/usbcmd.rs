@@ -0,0 +1,150 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use core::convert::TryFrom;
+use alloc::vec::Vec;
+
+use crate::error::DecodeError;
+
+use crate::cmd::prelude::*;
+
+
+/// The longest request frame, WriteFWData with a full 256 byte chunk.
+pub const MAX_REQUEST_LEN: usize = 2 + 4 + 256 + 2;
+
+// the enum and its codec are generated from the table in cmd/protocol.rs.
+pub use crate::cmd::protocol::UsbCmd;
+
+impl TryFrom<Vec<u8>> for UsbCmd {
+    type Error = DecodeError;
+
+    fn try_from(v: Vec<u8>) -> Result<Self, Self::Error> {
+        UsbCmd::try_from(v.as_slice())
+    }
+}
+
+// checks the CRC16 and returns what is between the group byte and it.
+pub(crate) fn strip_frame(v: &[u8]) -> Result<&[u8], DecodeError> {
+    validate_crc16(v)?;
+    Ok(&v[1..(v.len() - 2)])
+}
+
+fn validate_crc16(v: &[u8]) -> Result<(), DecodeError> {
+        // group || crc16 at the very least.
+        if v.len() < 3 {
+            return Err(DecodeError::Truncated{ needed: 3, got: v.len() });
+        }
+        let data = &v[0..(v.len() - 2)]; // strip crc16 off end.
+//        println!("data={:#?}", data);
+	let crc_cal = CRC16::calculate(&data);
+        if let Some(crc_data) = v.get((v.len() - 2)..v.len()) {
+            let crc = ((crc_data[1] as u16) << 8) | crc_data[0] as u16;
+            if crc != crc_cal.as_u16() {
+                return Err(DecodeError::CrcMismatch{ expected: crc_cal.as_u16(), got: crc });
+            }
+        } else {
+            return Err(DecodeError::Truncated{ needed: 2, got: v.len() });
+        }
+
+        Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use crate::encoder::TechAirEncoder;
+    use proptest::prelude::*;
+
+    // appends a valid crc16 so the bytes make it past validate_crc16().
+    fn with_crc(mut v: Vec<u8>) -> Vec<u8> {
+        let crc = CRC16::calculate(&v).as_u16();
+        v.push((crc & 0xff) as u8);
+        v.push((crc >>   8) as u8);
+        v
+    }
+
+    #[test]
+    fn short_frames_are_errors() {
+        for v in &[vec![], vec![0x00], vec![0x00, 0x01]] {
+            assert!(matches!(UsbCmd::try_from(v.clone()), Err(DecodeError::Truncated{ .. })));
+            assert!(matches!(UsbCmd::decode_request(v), Err(DecodeError::Truncated{ .. })));
+        }
+        assert!(matches!(UsbCmd::try_from(with_crc(vec![0x04])), Err(DecodeError::MissingSubcommand(0x04))));
+    }
+    #[test]
+    fn encodes_into_a_slice() {
+        let cmd = UsbCmd::SWUpdate(SWUpdateCmd::CRCCheck(0xaabb));
+        let mut buf = [0u8; MAX_REQUEST_LEN];
+        let len = cmd.encode_to_slice(&mut buf).unwrap();
+        assert_eq!(buf[..len], [0x20, 0x05, 0xaa, 0xbb, 0x25, 0x36]);
+        let err = cmd.encode_to_slice(&mut buf[..5]).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::WriteZero);
+
+        let fw = FWData::new(&[0x5a; 256], &1).unwrap();
+        let cmd = UsbCmd::SWUpdate(SWUpdateCmd::WriteFWData(fw));
+        assert_eq!(cmd.encode_to_slice(&mut buf).unwrap(), MAX_REQUEST_LEN);
+        assert_eq!(UsbCmd::decode_request(&buf).unwrap(), cmd);
+    }
+    #[test]
+    fn counted_text_must_fit_its_length_byte() {
+        let info = |n| UsbCmd::General(GeneralCmd::SetCustomerInfo(Setting::Value("x".repeat(n))));
+        assert!(info(255).encode(&mut Vec::new()).is_ok());
+        let err = info(256).encode(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+    #[test]
+    fn op_hours_must_be_hhh_mm_ss() {
+        use crate::encoder::TechAirResponseEncoder;
+
+        let hours = |h: &str| UsbCmd::Logging(LoggingCmd::GetOPHours(Some(h.into())));
+        let mut buf = Vec::new();
+        hours("123:45:06").encode_response(&mut buf).unwrap();
+        assert_eq!(buf[2..6], [0x00, 0x7b, 0x2d, 0x06]);
+        for bad in &["", "12:34", "1:2:3:4", "12:xx:56", "12:60:00", "12:00:60", "65536:00:00"] {
+            let err = hours(bad).encode_response(&mut Vec::new()).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput, "{}", bad);
+        }
+    }
+    #[test]
+    fn truncated_payloads_are_errors() {
+        // accel needs 6 bytes.
+        match UsbCmd::try_from(with_crc(vec![0x04, 0x02, 0x00, 0x01, 0x00, 0x02])) {
+            Err(DecodeError::ShortPayload{ group: 0x04, sub: 0x02, needed: 6, got: 4 }) => {},
+            r => panic!("{:?}", r),
+        }
+        // there is no bootloader state 0x42.
+        assert!(matches!(UsbCmd::try_from(with_crc(vec![0x20, 0x02, 0x42])),
+                         Err(DecodeError::InvalidPayload{ group: 0x20, sub: 0x02 })));
+    }
+
+    proptest! {
+        #[test]
+        fn decode_never_panics(v in proptest::collection::vec(any::<u8>(), 0..64)) {
+            let _ = UsbCmd::try_from(v.clone());
+            let _ = UsbCmd::decode_request(&v);
+        }
+        #[test]
+        fn decode_valid_crc_never_panics(group in prop_oneof![0x00..0x07u8, Just(0x20u8)],
+                                         body in proptest::collection::vec(any::<u8>(), 0..64)) {
+            let mut v = vec![group];
+            v.extend(body);
+            let v = with_crc(v);
+            let _ = UsbCmd::try_from(v.clone());
+            let _ = UsbCmd::decode_request(&v);
+        }
+    }
+}
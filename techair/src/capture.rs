@@ -0,0 +1,163 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+
+// Recording of the frames exchanged with an ACU.
+//
+// A capture is plain text, one frame per line:
+//
+//   # techair capture v1
+//   0.000412 > 00 02 80 71
+//   0.003107 < 00 02 01 31 dd 3b
+//
+// i.e. seconds since the capture started, '>' for host -> ACU and '<' for
+// ACU -> host, then the frame in hex including its CRC16. That keeps them
+// small enough to paste into a bug report and easy to edit by hand.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, LineWriter, Write};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::error::TechAirError;
+use crate::frame::{Direction, Frame};
+
+
+const MAGIC: &str = "# techair capture v1";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CaptureEntry {
+    pub at: Duration,
+    pub dir: Direction,
+    pub frame: Vec<u8>,
+}
+
+impl CaptureEntry {
+    /// Decodes the frame the way it was travelling.
+    pub fn decode(&self) -> Result<Frame, TechAirError> {
+        Ok(Frame::decode(self.dir, &self.frame)?)
+    }
+}
+
+impl fmt::Display for CaptureEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let arrow = match self.dir {
+            Direction::Request  => '>',
+            Direction::Response => '<',
+        };
+        write!(f, "{}.{:06} {}", self.at.as_secs(), self.at.subsec_micros(), arrow)?;
+        for b in &self.frame {
+            write!(f, " {:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for CaptureEntry {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = s.split_whitespace();
+        let at = fields.next()
+            .and_then(|t| t.parse::<f64>().ok())
+            .filter(|t| *t >= 0.0)
+            .ok_or_else(|| format!("bad timestamp in '{}'", s))?;
+        let dir = match fields.next() {
+            Some(">") => Direction::Request,
+            Some("<") => Direction::Response,
+            _ => return Err(format!("bad direction in '{}'", s)),
+        };
+        let frame = fields.map(|b| u8::from_str_radix(b, 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|e| format!("bad byte in '{}': {}", s, e))?;
+        Ok(CaptureEntry{ at: Duration::from_secs_f64(at), dir, frame })
+    }
+}
+
+/// Appends every frame it is given to a capture file.
+pub struct Capture {
+    out: Box<dyn Write + Send>,
+    start: Instant,
+}
+
+impl Capture {
+    pub fn create(path: &str) -> io::Result<Capture> {
+        Capture::new(LineWriter::new(File::create(path)?))
+    }
+
+    pub fn new<W: Write + Send + 'static>(out: W) -> io::Result<Capture> {
+        let mut out: Box<dyn Write + Send> = Box::new(out);
+        writeln!(out, "{}", MAGIC)?;
+        Ok(Capture{ out, start: Instant::now() })
+    }
+
+    pub fn record(&mut self, dir: Direction, frame: &[u8]) -> io::Result<()> {
+        let e = CaptureEntry{ at: self.start.elapsed(), dir, frame: frame.to_vec() };
+        writeln!(self.out, "{}", e)
+    }
+}
+
+/// Parses a capture, skipping blank lines and `#` comments.
+pub fn read_capture<R: BufRead>(r: R) -> io::Result<Vec<CaptureEntry>> {
+    let mut entries = Vec::new();
+    for (n, line) in r.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let e = line.parse::<CaptureEntry>().map_err(|e|
+            io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", n + 1, e)))?;
+        entries.push(e);
+    }
+    Ok(entries)
+}
+
+pub fn load_capture(path: &str) -> io::Result<Vec<CaptureEntry>> {
+    read_capture(BufReader::new(File::open(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::prelude::*;
+    use crate::usbcmd::UsbCmd;
+
+    #[test]
+    fn entry_round_trip() {
+        let e = CaptureEntry{
+            at: Duration::from_micros(3_000_412),
+            dir: Direction::Response,
+            frame: vec![0x00, 0x02, 0x01, 0x31, 0xdd, 0x3b],
+        };
+        assert_eq!(e.to_string(), "3.000412 < 00 02 01 31 dd 3b");
+        assert_eq!(e.to_string().parse::<CaptureEntry>().unwrap(), e);
+    }
+    #[test]
+    fn reads_capture() {
+        let text = format!("{}\n\n0.000100 > 00 02 80 71\n# comment\n0.001 < 00 02 01 31 dd 3b\n", MAGIC);
+        let entries = read_capture(text.as_bytes()).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].dir, Direction::Request);
+        assert_eq!(entries[1].frame, [0x00, 0x02, 0x01, 0x31, 0xdd, 0x3b]);
+        assert_eq!(entries[0].decode().unwrap().cmd, UsbCmd::General(GeneralCmd::GetSoftwareVersion(None)));
+        // made up by hand, the CRC16 is wrong.
+        assert!(matches!(entries[1].decode(), Err(TechAirError::CrcMismatch{ .. })));
+        let err = read_capture("0.1 ? 00\n".as_bytes()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
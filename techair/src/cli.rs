@@ -15,88 +15,55 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
 
 use crate::crypto::FwFile;
+use crate::error::TechAirError;
+use crate::query;
 use crate::serial::TechAir;
-use crate::usbcmd::UsbCmd;
+use crate::session::Acu;
+use crate::transport::{SerialTransport, Transport};
 use crate::cmd::prelude::*;
 
-use std::io::{Error, ErrorKind};
-use std::time::Duration;
+pub use crate::session::{MeasureVoltageType, SensorRevisionType};
+
 
 /////////
 /// General.
 /// //.
 
-pub fn get_sw_version() -> Option<String> {
-    let mut ta = TechAir::new().unwrap();
-    println!("{:#?}", ta);
-    // gets sw version
-    if ta.write(UsbCmd::General(GeneralCmd::GetSoftwareVersion(None))).is_err() {
-        return None;
-    }
-    if let Ok(packet) = ta.read() {
-        println!("packet = {:#?}", packet);
-        if let Some(GeneralCmd::GetSoftwareVersion(ver)) = packet.general() {
-            return ver.map(|v| v.to_string());
-        }
-    }
-    None
-}
-pub fn get_operating_modus() -> Option<OpModus> {
-    let mut ta = TechAir::new().unwrap();
-    println!("{:#?}", ta);
-    // gets sw version
-    if ta.write(UsbCmd::General(GeneralCmd::GetOperatingModus(None))).is_err() {
-        return None;
-    }
-    if let Ok(packet) = ta.read() {
-        println!("packet = {:#?}", packet);
-        if let Some(GeneralCmd::GetOperatingModus(modus)) = packet.general() {
-            return modus;
-        }
-    }
-    None
-}
-pub fn get_serial_nr() -> Option<String> {
-    let mut ta = TechAir::new().unwrap();
-    // gets serial number
-    if ta.write(UsbCmd::General(GeneralCmd::GetSerialNr(None))).is_err() {
-        println!("write went bad!");
-        return None;
-    }
-    println!("reading..");
-    if let Ok(packet) = ta.read() {
-        println!("read!");
-        if let Some(GeneralCmd::GetSerialNr(ver)) = packet.general() {
-            return ver;
-        }
-    }
-    None
+pub fn get_sw_version<T: Transport>(acu: &mut Acu<T>) -> Result<String, TechAirError> {
+    acu.sw_version().map(|v| v.to_string())
 }
-pub fn get_customer_info() -> Option<String> {
-    let mut ta = TechAir::new().unwrap();
-    // gets customer info
-    if ta.write(UsbCmd::General(GeneralCmd::GetCustomerInfo(None))).is_err() {
-        return None;
-    }
-    if let Ok(packet) = ta.read() {
-        if let Some(GeneralCmd::GetCustomerInfo(info)) = packet.general() {
-            return info;
-        }
-    }
-    None
+pub fn get_operating_modus<T: Transport>(acu: &mut Acu<T>) -> Result<OpModus, TechAirError> {
+    acu.operating_modus()
 }
-pub fn get_service_date() -> Option<String> {
-    let mut ta = TechAir::new().unwrap();
-    // gets service date
-    if ta.write(UsbCmd::General(GeneralCmd::GetServiceDate(None))).is_err() {
-        return None;
-    }
-    if let Ok(packet) = ta.read() {
-        if let Some(GeneralCmd::GetServiceDate(date)) = packet.general() {
-            return date;
-        }
-    }
-    None
+pub fn get_serial_nr<T: Transport>(acu: &mut Acu<T>) -> Result<String, TechAirError> {
+    acu.serial_nr()
+}
+pub fn get_customer_info<T: Transport>(acu: &mut Acu<T>) -> Result<String, TechAirError> {
+    acu.customer_info()
+}
+pub fn get_service_date<T: Transport>(acu: &mut Acu<T>) -> Result<Option<ServiceDate>, TechAirError> {
+    acu.service_date()
+}
+pub fn set_serial_nr<T: Transport>(acu: &mut Acu<T>, serial: &str) -> Result<(), TechAirError> {
+    acu.set_serial_nr(serial)
+}
+pub fn set_customer_info<T: Transport>(acu: &mut Acu<T>, info: &str) -> Result<(), TechAirError> {
+    acu.set_customer_info(info)
+}
+pub fn set_service_date<T: Transport>(acu: &mut Acu<T>, date: ServiceDate) -> Result<(), TechAirError> {
+    acu.set_service_date(date)
+}
+pub fn get_hw_version<T: Transport>(acu: &mut Acu<T>) -> Result<HardwareVersion, TechAirError> {
+    acu.hw_version()
+}
+pub fn set_hw_version<T: Transport>(acu: &mut Acu<T>, version: HardwareVersion) -> Result<(), TechAirError> {
+    acu.set_hw_version(version)
+}
+pub fn get_ctrl_mode<T: Transport>(acu: &mut Acu<T>) -> Result<CtrlMode, TechAirError> {
+    acu.ctrl_mode()
+}
+pub fn set_ctrl_mode<T: Transport>(acu: &mut Acu<T>, mode: CtrlMode) -> Result<(), TechAirError> {
+    acu.set_ctrl_mode(mode)
 }
 
 
@@ -104,132 +71,44 @@ pub fn get_service_date() -> Option<String> {
 /// Logging.
 /// //.
 
-pub fn get_op_hours() -> Option<String> {
-    let mut ta = TechAir::new().unwrap();
-    println!("{:#?}", ta);
-    if ta.write(UsbCmd::Logging(LoggingCmd::GetOPHours(None))).is_err() {
-        return None;
-    }
-    if let Ok(packet) = ta.read() {
-        if let Some(LoggingCmd::GetOPHours(hours)) = packet.logging() {
-            return hours;
-        }
-    }
-    None
-}
-pub fn clear_op_hours() -> Result<(), std::io::Error> {
-    let mut ta = TechAir::new().unwrap();
-    println!("{:#?}", ta);
-    ta.write(UsbCmd::Logging(LoggingCmd::ClearOPHours))
-}
-pub fn get_no_of_errors() -> Option<u8> {
-    let mut ta = TechAir::new().unwrap();
-    println!("{:#?}", ta);
-    if ta.write(UsbCmd::Logging(LoggingCmd::GetNumOfErrors(None))).is_err() {
-        return None;
-    }
-    if let Ok(packet) = ta.read() {
-        if let Some(LoggingCmd::GetNumOfErrors(errors)) = packet.logging() {
-            return errors;
-        }
-    }
-    None
+pub fn get_op_hours<T: Transport>(acu: &mut Acu<T>) -> Result<String, TechAirError> {
+    acu.op_hours()
+}
+pub fn clear_op_hours<T: Transport>(acu: &mut Acu<T>) -> Result<(), TechAirError> {
+    acu.clear_op_hours()
+}
+pub fn get_no_of_errors<T: Transport>(acu: &mut Acu<T>) -> Result<u8, TechAirError> {
+    acu.no_of_errors()
 }
 //GetErrorEntry,
-pub fn clear_error_history() -> Result<(), std::io::Error> {
-    let mut ta = TechAir::new().unwrap();
-    println!("{:#?}", ta);
-    ta.write(UsbCmd::Logging(LoggingCmd::ClearErrorHistory))
-}
-pub fn get_no_of_precrash_logs() -> Option<u16> {
-    let mut ta = TechAir::new().unwrap();
-    println!("{:#?}", ta);
-    if ta.write(UsbCmd::Logging(LoggingCmd::GetNumOfPreCrashLogs(None))).is_err() {
-        return None;
-    }
-    if let Ok(packet) = ta.read() {
-        if let Some(LoggingCmd::GetNumOfPreCrashLogs(nlogs)) = packet.logging() {
-            return nlogs;
-        }
-    }
-    None
+pub fn clear_error_history<T: Transport>(acu: &mut Acu<T>) -> Result<(), TechAirError> {
+    acu.clear_error_history()
 }
-pub fn get_precrash_logs() -> Option<Vec::<u8>> {
-    let mut ta = TechAir::new().unwrap();
-    println!("{:#?}", ta);
-    if ta.write(UsbCmd::Logging(LoggingCmd::GetPreCrashLogs(None))).is_err() {
-        return None;
-    }
-    if let Ok(packet) = ta.read() {
-        if let Some(LoggingCmd::GetPreCrashLogs(logs)) = packet.logging() {
-            return logs;
-        }
-    }
-    None
-}
-pub fn clear_precrash_log() -> Result<(), std::io::Error> {
-    let mut ta = TechAir::new().unwrap();
-    println!("{:#?}", ta);
-    ta.write(UsbCmd::Logging(LoggingCmd::ClearPreCrashLog))
-}
-pub fn get_no_of_postcrash_logs() -> Option<u16> {
-    let mut ta = TechAir::new().unwrap();
-    println!("{:#?}", ta);
-    if ta.write(UsbCmd::Logging(LoggingCmd::GetNumOfPostCrashLogs(None))).is_err() {
-        return None;
-    }
-    if let Ok(packet) = ta.read() {
-        if let Some(LoggingCmd::GetNumOfPostCrashLogs(nlogs)) = packet.logging() {
-            return nlogs;
-        }
-    }
-    None
+pub fn get_no_of_precrash_logs<T: Transport>(acu: &mut Acu<T>) -> Result<u16, TechAirError> {
+    acu.no_of_precrash_logs()
+}
+pub fn get_precrash_logs<T: Transport>(acu: &mut Acu<T>) -> Result<Vec::<u8>, TechAirError> {
+    acu.precrash_logs()
+}
+pub fn clear_precrash_log<T: Transport>(acu: &mut Acu<T>) -> Result<(), TechAirError> {
+    acu.clear_precrash_log()
+}
+pub fn get_no_of_postcrash_logs<T: Transport>(acu: &mut Acu<T>) -> Result<u16, TechAirError> {
+    acu.no_of_postcrash_logs()
 }
 //GetPostCrashBulk,
-pub fn clear_postcrash_log() -> Result<(), std::io::Error> {
-    let mut ta = TechAir::new().unwrap();
-    println!("{:#?}", ta);
-    ta.write(UsbCmd::Logging(LoggingCmd::ClearPostCrashLog))
+pub fn clear_postcrash_log<T: Transport>(acu: &mut Acu<T>) -> Result<(), TechAirError> {
+    acu.clear_postcrash_log()
 }
 //GetPreCrashBulk,
-pub fn get_error_history() -> Option<Vec::<u8>> {
-    let mut ta = TechAir::new().unwrap();
-    println!("{:#?}", ta);
-    if ta.write(UsbCmd::Logging(LoggingCmd::GetErrorHistory(None))).is_err() {
-        return None;
-    }
-    if let Ok(packet) = ta.read() {
-        if let Some(LoggingCmd::GetErrorHistory(errors)) = packet.logging() {
-            return errors;
-        }
-    }
-    None
+pub fn get_error_history<T: Transport>(acu: &mut Acu<T>) -> Result<Vec::<u8>, TechAirError> {
+    acu.error_history()
 }
-pub fn get_postcrash_logs() -> Option<Vec::<u8>> {
-    let mut ta = TechAir::new().unwrap();
-    println!("{:#?}", ta);
-    if ta.write(UsbCmd::Logging(LoggingCmd::GetPostCrashLogs(None))).is_err() {
-        return None;
-    }
-    if let Ok(packet) = ta.read() {
-        if let Some(LoggingCmd::GetPostCrashLogs(logs)) = packet.logging() {
-            return logs;
-        }
-    }
-    None
+pub fn get_postcrash_logs<T: Transport>(acu: &mut Acu<T>) -> Result<Vec::<u8>, TechAirError> {
+    acu.postcrash_logs()
 }
-pub fn get_bat_count() -> Option<u16> {
-    let mut ta = TechAir::new().unwrap();
-    println!("{:#?}", ta);
-    if ta.write(UsbCmd::Logging(LoggingCmd::GetBatCount(None))).is_err() {
-        return None;
-    }
-    if let Ok(packet) = ta.read() {
-        if let Some(LoggingCmd::GetBatCount(bcount)) = packet.logging() {
-            return bcount;
-        }
-    }
-    None
+pub fn get_bat_count<T: Transport>(acu: &mut Acu<T>) -> Result<u16, TechAirError> {
+    acu.bat_count()
 }
 //GetPreCrashENCBulk,
 //GetPostCrashENCBulk,
@@ -239,150 +118,19 @@ pub fn get_bat_count() -> Option<u16> {
 /// Measure.
 /// //.
 
-pub fn get_charging_state() -> Option<String> {
-    let mut ta = TechAir::new().unwrap();
-    // gets the charging state
-    let p = UsbCmd::Measure(MeasureCmd::GetChargingState(None));
-    if ta.write(p).is_err() {
-        return None;
-    }
-    if let Ok(packet) = ta.read() {
-        if let Some(MeasureCmd::GetChargingState(data)) = packet.measure() {
-            return data;
-        }
-    }
-    None
-}
-pub fn get_zip_state() -> Option<bool> {
-    let mut ta = TechAir::new().unwrap();
-    // gets the zip state
-    let p = UsbCmd::Measure(MeasureCmd::GetZIPSwitchState(false));
-    if ta.write(p).is_err() {
-        return None;
-    }
-    if let Ok(packet) = ta.read() {
-        if let Some(MeasureCmd::GetZIPSwitchState(data)) = packet.measure() {
-            return Some(data);
-        }
-    }
-    None
-}
-pub fn set_led_state(state: u8) {
-    let mut ta = TechAir::new().unwrap();
-    let p = UsbCmd::Measure(MeasureCmd::SetEXTDisplay(state)); // USBSetLEDs()
-    ta.write(p).unwrap();
-    if let Ok(packet) = ta.read() {
-        if let Some(MeasureCmd::SetEXTDisplay(data)) = packet.measure() {
-            println!("SetEXTDisplay data ret={}", data);
-        }
-    }
+pub fn get_charging_state<T: Transport>(acu: &mut Acu<T>) -> Result<String, TechAirError> {
+    acu.charging_state()
 }
-
-pub enum MeasureVoltageType {
-    Logic,
-    Peripheral,
-    RightHand,
-    LeftHand,
-    RightFoot,
-    LeftFoot,
-    Squib,
-    Battery,
-}
-
-pub fn get_measure_voltage(mvt: MeasureVoltageType) -> Option<f32> {
-    let mut ta = TechAir::new().unwrap();
-    // gets voltage
-    match mvt {
-        MeasureVoltageType::Logic => {
-            let p = UsbCmd::Measure(MeasureCmd::GetLogicVoltage(0.00));
-            if ta.write(p).is_err() {
-                return None;
-            }
-            if let Ok(packet) = ta.read() {
-                if let Some(MeasureCmd::GetLogicVoltage(data)) = packet.measure() {
-                    return Some(data);
-                }
-            }
-        },
-        MeasureVoltageType::Peripheral => {
-            let p = UsbCmd::Measure(MeasureCmd::GetPeripheralVoltage(0.00));
-            if ta.write(p).is_err() {
-                return None;
-            }
-            if let Ok(packet) = ta.read() {
-                if let Some(MeasureCmd::GetPeripheralVoltage(data)) = packet.measure() {
-                    return Some(data);
-                }
-            }
-        },
-        MeasureVoltageType::RightHand => {
-            let p = UsbCmd::Measure(MeasureCmd::GetRightHandVoltage(0.00));
-            if ta.write(p).is_err() {
-                return None;
-            }
-            if let Ok(packet) = ta.read() {
-                if let Some(MeasureCmd::GetRightHandVoltage(data)) = packet.measure() {
-                    return Some(data);
-                }
-            }
-        },
-        MeasureVoltageType::LeftHand => {
-            let p = UsbCmd::Measure(MeasureCmd::GetLeftHandVoltage(0.00));
-            if ta.write(p).is_err() {
-                return None;
-            }
-            if let Ok(packet) = ta.read() {
-                if let Some(MeasureCmd::GetLeftHandVoltage(data)) = packet.measure() {
-                    return Some(data);
-                }
-            }
-        },
-        MeasureVoltageType::RightFoot => {
-            let p = UsbCmd::Measure(MeasureCmd::GetRightFootVoltage(0.00));
-            if ta.write(p).is_err() {
-                return None;
-            }
-            if let Ok(packet) = ta.read() {
-                if let Some(MeasureCmd::GetRightFootVoltage(data)) = packet.measure() {
-                    return Some(data);
-                }
-            }
-        },
-        MeasureVoltageType::LeftFoot => {
-            let p = UsbCmd::Measure(MeasureCmd::GetLeftFootVoltage(0.00));
-            if ta.write(p).is_err() {
-                return None;
-            }
-            if let Ok(packet) = ta.read() {
-                if let Some(MeasureCmd::GetLeftFootVoltage(data)) = packet.measure() {
-                    return Some(data);
-                }
-            }
-        },
-        MeasureVoltageType::Squib => {
-            let p = UsbCmd::Measure(MeasureCmd::GetSquibVoltage(0.00));
-            if ta.write(p).is_err() {
-                return None;
-            }
-            if let Ok(packet) = ta.read() {
-                if let Some(MeasureCmd::GetSquibVoltage(data)) = packet.measure() {
-                    return Some(data);
-                }
-            }
-        },
-        MeasureVoltageType::Battery => {
-            let p = UsbCmd::Measure(MeasureCmd::GetBatteryVoltage(0.00));
-            if ta.write(p).is_err() {
-                return None;
-            }
-            if let Ok(packet) = ta.read() {
-                if let Some(MeasureCmd::GetBatteryVoltage(data)) = packet.measure() {
-                    return Some(data);
-                }
-            }
-        },
-    }
-    None
+pub fn get_zip_state<T: Transport>(acu: &mut Acu<T>) -> Result<bool, TechAirError> {
+    acu.zip_closed()
+}
+pub fn set_led_state<T: Transport>(acu: &mut Acu<T>, state: u8) -> Result<(), TechAirError> {
+    let data = acu.set_led_state(state)?; // USBSetLEDs()
+    debug!("SetEXTDisplay data ret={}", data);
+    Ok(())
+}
+pub fn get_measure_voltage<T: Transport>(acu: &mut Acu<T>, mvt: MeasureVoltageType) -> Result<f32, TechAirError> {
+    acu.voltage(mvt)
 }
 
 
@@ -390,74 +138,12 @@ pub fn get_measure_voltage(mvt: MeasureVoltageType) -> Option<f32> {
 /// Sensor.
 /// //.
 
-pub enum SensorRevisionType {
-    RightHand,
-    LeftHand,
-    RightFoot,
-    LeftFoot,
-}
-
-pub fn get_sensor_revision(srt: SensorRevisionType) -> Option<(f32, f32)> {
-    let mut ta = TechAir::new().unwrap();
+pub fn get_sensor_revision<T: Transport>(acu: &mut Acu<T>, srt: SensorRevisionType) -> Result<(f32, f32), TechAirError> {
     // enable sensor readings
     let mask = Some(0x01 | 0x05 | 0x15 | 0x35); // found via transaction traces?
-    let p = UsbCmd::Sensor(SensorCmd::EnableSensorReading(mask)); // FIXME: this should consume a enable mask..
-    if ta.write(p).is_err() {
-        return None;
-    }
-    if let Ok(packet) = ta.read() {
-        if let Some(SensorCmd::EnableSensorReading(data)) = packet.sensor() {
-            println!("EnableSensorReading data = {:?}", data);
-        }
-    }
+    acu.enable_sensor_reading(mask)?; // FIXME: this should consume a enable mask..
     // gets revision info
-    match srt {
-        SensorRevisionType::RightHand => {
-            let p = UsbCmd::Sensor(SensorCmd::GetSWVRH((0.00, 0.00)));
-            if ta.write(p).is_err() {
-                return None;
-            }
-            if let Ok(packet) = ta.read() {
-                if let Some(SensorCmd::GetSWVRH(data)) = packet.sensor() {
-                    return Some(data);
-                }
-            }
-        },
-        SensorRevisionType::LeftHand => {
-            let p = UsbCmd::Sensor(SensorCmd::GetSWVLH((0.00, 0.00)));
-            if ta.write(p).is_err() {
-                return None;
-            }
-            if let Ok(packet) = ta.read() {
-                if let Some(SensorCmd::GetSWVLH(data)) = packet.sensor() {
-                    return Some(data);
-                }
-            }
-        },
-        SensorRevisionType::RightFoot => {
-            let p = UsbCmd::Sensor(SensorCmd::GetSWVRF((0.00, 0.00)));
-            if ta.write(p).is_err() {
-                return None;
-            }
-            if let Ok(packet) = ta.read() {
-                if let Some(SensorCmd::GetSWVRF(data)) = packet.sensor() {
-                    return Some(data);
-                }
-            }
-        },
-        SensorRevisionType::LeftFoot => {
-            let p = UsbCmd::Sensor(SensorCmd::GetSWVLF((0.00, 0.00)));
-            if ta.write(p).is_err() {
-                return None;
-            }
-            if let Ok(packet) = ta.read() {
-                if let Some(SensorCmd::GetSWVLF(data)) = packet.sensor() {
-                    return Some(data);
-                }
-            }
-        },
-    }
-    None
+    acu.sensor_revision(srt)
 }
 
 
@@ -465,30 +151,14 @@ pub fn get_sensor_revision(srt: SensorRevisionType) -> Option<(f32, f32)> {
 /// Algorithm.
 /// //.
 
-pub fn init_algorithm() -> Result<(), std::io::Error> {
-    let mut ta = TechAir::new().unwrap();
-    println!("{:#?}", ta);
-    // init_algorithm
-    ta.write(UsbCmd::Algorithm(AlgorithmCmd::InitAlgorithm))
-}
-pub fn get_algorithm_thresholds() -> Option<Vec::<u8>> {
-    let mut ta = TechAir::new().unwrap();
-    println!("{:#?}", ta);
-    if ta.write(UsbCmd::Algorithm(AlgorithmCmd::GetAlogrithmThresholds(None))).is_err() {
-        return None;
-    }
-    if let Ok(packet) = ta.read() {
-        if let Some(AlgorithmCmd::GetAlogrithmThresholds(th)) = packet.algorithm() {
-            return th;
-        }
-    }
-    None
+pub fn init_algorithm<T: Transport>(acu: &mut Acu<T>) -> Result<(), TechAirError> {
+    acu.init_algorithm()
+}
+pub fn get_algorithm_thresholds<T: Transport>(acu: &mut Acu<T>) -> Result<Vec::<u8>, TechAirError> {
+    acu.algorithm_thresholds()
 }
-pub fn set_algorithm_default_thresholds() -> Result<(), std::io::Error> {
-    let mut ta = TechAir::new().unwrap();
-    println!("{:#?}", ta);
-    // set_algorithm_default_thresholds
-    ta.write(UsbCmd::Algorithm(AlgorithmCmd::SetAlogrithmDefaultThresholds(None)))
+pub fn set_algorithm_default_thresholds<T: Transport>(acu: &mut Acu<T>) -> Result<(), TechAirError> {
+    acu.set_algorithm_default_thresholds()
 }
 
 
@@ -496,47 +166,22 @@ pub fn set_algorithm_default_thresholds() -> Result<(), std::io::Error> {
 /// Airbag.
 /// //.
 
-pub fn get_calib_squib_res() -> (Option<f32>, Option<f32>) {
-    let mut ta = TechAir::new().unwrap();
-    println!("{:#?}", ta);
-    // gets GetCalibSquibRes()
-    if ta.write(UsbCmd::Airbag(AirbagCmd::GetCalibSquibRes( (None, None) ))).is_err() {
-        return (None, None);
-    }
-    if let Ok(packet) = ta.read() {
-        println!("packet = {:#?}", packet);
-        if let Some(AirbagCmd::GetCalibSquibRes(squibres)) = packet.airbag() {
-            return squibres;
-        }
-    }
-    (None, None)
-}
-pub fn get_inflation_type() -> Option<String> {
-    let mut ta = TechAir::new().unwrap();
-    println!("{:#?}", ta);
-    // gets inflation type
-    if ta.write(UsbCmd::Airbag(AirbagCmd::GetInflationType(0x00))).is_err() {
-        return None;
-    }
-    if let Ok(packet) = ta.read() {
-        println!("packet = {:#?}", packet);
-        if let Some(AirbagCmd::GetInflationType(inftyp)) = packet.airbag() {
-            println!("inflation type = {:?}", inftyp);
-            match inftyp {
-                0x44 => return Some("single".to_string()),
-                0xb4 => return Some("double-race".to_string()),
-                0xbb => return Some("double".to_string()),
-                0xff => return Some("clear".to_string()),
-                _    => return None,
-            }
-        }
+pub fn get_calib_squib_res<T: Transport>(acu: &mut Acu<T>) -> Result<(Option<f32>, Option<f32>), TechAirError> {
+    acu.calib_squib_res()
+}
+pub fn get_inflation_type<T: Transport>(acu: &mut Acu<T>) -> Result<String, TechAirError> {
+    let inftyp = acu.inflation_type()?;
+    debug!("inflation type = {:?}", inftyp);
+    match inftyp {
+        0x44 => Ok("single".to_string()),
+        0xb4 => Ok("double-race".to_string()),
+        0xbb => Ok("double".to_string()),
+        0xff => Ok("clear".to_string()),
+        _    => Err(TechAirError::InvalidPayload{ group: 0x06, sub: 0x0a }),
     }
-    None
 }
-pub fn set_inflation_type(it: u8) -> Result<(), std::io::Error> {
-    let mut ta = TechAir::new().unwrap();
-    println!("{:#?}", ta);
-    ta.write(UsbCmd::Airbag(AirbagCmd::SetInflationType(it)))
+pub fn set_inflation_type<T: Transport>(acu: &mut Acu<T>, it: u8) -> Result<(), TechAirError> {
+    acu.set_inflation_type(it)
 }
 
 
@@ -544,14 +189,14 @@ pub fn set_inflation_type(it: u8) -> Result<(), std::io::Error> {
 /// SWUpdate.
 /// //.
 
-pub struct SWUpdateBootloader {
-    ta: TechAir,
+pub struct SWUpdateBootloader<'a, T: Transport = SerialTransport> {
+    ta: &'a mut TechAir<T>,
 }
 
-impl SWUpdateBootloader {
-    pub fn new() -> SWUpdateBootloader {
-        let mut ta = TechAir::new().unwrap();
-        start_bootloader(&mut ta);
+impl<'a, T: Transport> SWUpdateBootloader<'a, T> {
+    pub fn new(acu: &'a mut Acu<T>) -> SWUpdateBootloader<'a, T> {
+        let ta = acu.techair();
+        start_bootloader(ta);
         SWUpdateBootloader{
             ta: ta,
         }
@@ -559,35 +204,17 @@ impl SWUpdateBootloader {
 
     pub fn get_swupdate_bootloader_version(&mut self) -> Option<u8> {
         // gets bootloader version
-        if self.ta.write(UsbCmd::SWUpdate(SWUpdateCmd::GetBootLoaderVersion( None ))).is_err() {
-            return None;
-        }
-        if let Ok(packet) = self.ta.read() {
-            println!("packet = {:#?}", packet);
-            if let Some(SWUpdateCmd::GetBootLoaderVersion(ver)) = packet.swupdate() {
-                return ver;
-            }
-        }
-        None
+        self.ta.query(query::GetBootLoaderVersion).ok()
     }
 
     pub fn get_swupdate_bootloader_state(&mut self) -> Option<SWUpdateBootLoaderStates> {
         // gets bootloader state
-        if self.ta.write(UsbCmd::SWUpdate(SWUpdateCmd::GetBootLoaderState( None ))).is_err() {
-            return None;
-        }
-        if let Ok(packet) = self.ta.read() {
-            println!("packet = {:#?}", packet);
-            if let Some(SWUpdateCmd::GetBootLoaderState(state)) = packet.swupdate() {
-                return state;
-            }
-        }
-        None
+        self.ta.query(query::GetBootLoaderState).ok()
     }
 
     //SWUpdateCmd::WriteFWData
     pub fn xfer_swupdate_bootloader_hexfile(&mut self, fwf: FwFile)
-        -> Result<(), std::io::Error> {
+        -> Result<(), TechAirError> {
         let mut hexfile = fwf.data();
         // as_chars(data: Vec::<u8>) -> Vec::<char>
         //
@@ -596,9 +223,9 @@ impl SWUpdateBootloader {
         hexfile.push((crc_fw >>   8) as u8);
         hexfile.push((crc_fw & 0xff) as u8);
         let hexfile_len = hexfile.len();
-        println!("writing out hexfile with len {:#04x?}", hexfile_len);
+        info!("writing out hexfile with len {:#04x?}", hexfile_len);
         let mut page_count = calculate_num_pages(hexfile_len);
-        println!("calculated a page_count of {:#04x?}", page_count);
+        debug!("calculated a page_count of {:#04x?}", page_count);
         // split buffer up and xfer hex data to bootloader
         for chunk in hexfile.chunks(256) {
             //println!("DEBUG: chunk len = {}", chunk.len());
@@ -613,16 +240,11 @@ impl SWUpdateBootloader {
                 // saturate to page_count = 0.
                 page_count = 0;
             }
-            self.ta.write(UsbCmd::SWUpdate(SWUpdateCmd::WriteFWData( fw_data )))?;
-            self.ta.bytes_to_write_left();
-            let packet = self.ta.read()?;
-            println!("packet = {:#?}", packet);
-            if let Some(SWUpdateCmd::WriteFWData(res)) = packet.swupdate() {
-                // if returned page_count != num of bytes sent, then fail!.
-                if res.page_count != page_count {
-                    println!("{} != {}", res.page_count, page_count);
-                    return Err(Error::new(ErrorKind::Other, "page_count fuck up!"));
-                }
+            let left = self.ta.query(query::WriteFWData(fw_data))?;
+            // if returned page_count != num of bytes sent, then fail!.
+            if left != page_count {
+                return Err(TechAirError::Firmware(
+                    format!("page_count {} != {}", left, page_count)));
             }
     // gets ctrl mode? 
 //    self.ta.write(UsbCmd::General(GeneralCmd::GetCtrlMode(None)))?;
@@ -633,12 +255,12 @@ impl SWUpdateBootloader {
 //        }
 //    }
             if let Some(state) = self.get_swupdate_bootloader_state() {
-                println!("state: {:?}", state);
+                debug!("state: {:?}", state);
             }
         }
         loop { // XXX
             if let Some(state) = self.get_swupdate_bootloader_state() {
-                println!("state: {:?}", state);
+                debug!("state: {:?}", state);
                 //state: CheckReadFW
                 //state: EraseFlash
                 //state: FlashFW
@@ -651,7 +273,7 @@ impl SWUpdateBootloader {
         // XXX
         self.crccheck_swupdate_bootloader_hexfile(fwf)?;
         if let Some(state) = self.get_swupdate_bootloader_state() {
-            println!("state: {:?}", state);
+            debug!("state: {:?}", state);
         }
 
         Ok(())
@@ -659,56 +281,39 @@ impl SWUpdateBootloader {
 
     //SWUpdateCmd::CRCCheck
     fn crccheck_swupdate_bootloader_hexfile(&mut self, fwf: FwFile)
-        -> Result<(), std::io::Error> {
+        -> Result<(), TechAirError> {
         let bl_ver = self.get_swupdate_bootloader_version().unwrap();
         let hex_crc16: u16 = crate::crypto::crc16_fw_data(fwf, bl_ver)?;
-        self.ta.write(UsbCmd::SWUpdate(SWUpdateCmd::CRCCheck( hex_crc16 )))?;
-        println!("reading back crc check");
-        self.ta.set_timeout(Duration::from_millis(3000))?;
-        let packet = self.ta.read()?;
-        if let Some(SWUpdateCmd::CRCCheck(ret)) = packet.swupdate() {
-            if ret == 0 {
-                return Ok(());
-            } else {
-                println!("xfer of fw fucked up! ret={}", ret);
-            }
+        info!("sending crc check");
+        let ret = self.ta.query(query::CRCCheck(hex_crc16))?;
+        if ret == 0 {
+            return Ok(());
         }
-        Err(Error::new(ErrorKind::Other, "xfer of fw fucked up!"))
+        debug!("xfer of fw fucked up! ret={}", ret);
+        Err(TechAirError::Firmware("CRC check of the flashed image failed".to_string()))
     }
 }
 
-impl Drop for SWUpdateBootloader {
+impl<T: Transport> Drop for SWUpdateBootloader<'_, T> {
     fn drop(&mut self) {
-        println!("SWUpdateBootloader::drop()");
-        quit_bootloader(&mut self.ta);
+        debug!("SWUpdateBootloader::drop()");
+        quit_bootloader(self.ta);
     }
 }
 
-fn start_bootloader(ta: &mut TechAir) {
+fn start_bootloader<T: Transport>(ta: &mut TechAir<T>) {
     //StartBootLoader,
-    println!("StartBootLoader()");
-    let p = UsbCmd::SWUpdate(SWUpdateCmd::StartBootLoader);
-    if ta.write(p).is_err() {
-        return;
-    }
-    if let Ok(packet) = ta.read() {
-        if let Some(SWUpdateCmd::StartBootLoader) = packet.swupdate() {
-            println!("StartBootLoader data");
-        }
+    debug!("StartBootLoader()");
+    if ta.query(query::StartBootLoader).is_ok() {
+        debug!("StartBootLoader data");
     }
 }
 
-fn quit_bootloader(ta: &mut TechAir) {
+fn quit_bootloader<T: Transport>(ta: &mut TechAir<T>) {
     //QuitBootLoader,
-    println!("QuitBootLoader()");
-    let p = UsbCmd::SWUpdate(SWUpdateCmd::QuitBootLoader);
-    if ta.write(p).is_err() {
-        return;
-    }
-    if let Ok(packet) = ta.read() {
-        if let Some(SWUpdateCmd::QuitBootLoader) = packet.swupdate() {
-            println!("QuitBootLoader data");
-        }
+    debug!("QuitBootLoader()");
+    if ta.query(query::QuitBootLoader).is_ok() {
+        debug!("QuitBootLoader data");
     }
 }
 
@@ -19,7 +19,7 @@ extern crate crypto;
 use std::str;
 //use std::error::Error;
 //use std::io::prelude::*;
-use std::io::{Read, Write};
+use std::io::{self, Read, Write};
 use std::fs::File;
 
 use crypto::{aes, buffer, blockmodes, symmetriccipher};
@@ -75,17 +75,23 @@ fn parse_ihex(ihex: &[u8], addr_space: (u32, u32)) -> Result<Vec::<u8>, std::io:
             continue; // winblows doesn't bother processing these lines.
         }
 //        println!("seg={:?}", seg);
-        let record = decode_hexline(seg).unwrap(); // assume line is ok :/
+        let record = match decode_hexline(seg) {
+            Some(r) => r,
+            None => return Err(io::Error::new(io::ErrorKind::InvalidData, "malformed line in hex file")),
+        };
 //        println!("record={:?}", record);
         let bcount = record[0]; // num5
 //        println!("bcount={}", bcount);
+        if record.len() < bcount as usize + 5 || bcount as usize > dseg.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "truncated line in hex file"));
+        }
         let mut crc_bsum: u32 = 0;
         for i in 0..bcount+5 {
             crc_bsum += record[i as usize] as u32;
         }
         if crc_bsum & 0xff != 0 {
-            println!("bcount={}, crc=0x{:02x}", bcount, (crc_bsum &0xff) as u8);
-            panic!("invalid crc in hex file");
+            debug!("bcount={}, crc=0x{:02x}", bcount, (crc_bsum &0xff) as u8);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid crc in hex file"));
         }
         //if record[(bcount+4) as usize] & 0xff == 0xff {
         //    println!("EOF record found! breaking..");
@@ -108,7 +114,7 @@ fn parse_ihex(ihex: &[u8], addr_space: (u32, u32)) -> Result<Vec::<u8>, std::io:
                 let rel_addr: u32 = base_addr - s;
                 if rel_addr <= e - s - (bcount as u32) {
                     for i in 0..bcount {
-                        println!("base_addr=0x{:x}: dseg[..]=0x{:x}", base_addr, dseg[i as usize]);
+                        trace!("base_addr=0x{:x}: dseg[..]=0x{:x}", base_addr, dseg[i as usize]);
                         hexdata_buf[(i as u32 + rel_addr) as usize] = dseg[i as usize];
                     }
                     continue; // we are done with this dseg.
@@ -133,7 +139,7 @@ fn parse_ihex(ihex: &[u8], addr_space: (u32, u32)) -> Result<Vec::<u8>, std::io:
         }
     }
     if bad_dseg == true {
-        panic!("bad deseg");
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "bad deseg"));
     }
     Ok(hexdata_buf)
 }
@@ -142,12 +148,12 @@ fn decode_hexline(data: &[&u8]) -> Option<Vec::<u8>> {
     if data.len() % 2 != 0 {
         return None;
     }
-    let x = data.into_iter().map(|&&b| decode_asciihex(b)).collect::<Vec::<u8>>();
+    let x = data.into_iter().map(|&&b| decode_asciihex(b)).collect::<Option<Vec::<u8>>>()?;
     Some(x.chunks(2).map(|a| (a[0] << 4) + a[1]).collect())
 }
 
-fn decode_asciihex(b: u8) -> u8 {
-    char::from(b).to_digit(16).unwrap() as u8
+fn decode_asciihex(b: u8) -> Option<u8> {
+    char::from(b).to_digit(16).map(|d| d as u8)
 }
 
 pub fn idk() -> Result<FwFile, std::io::Error> {
@@ -167,7 +173,7 @@ pub fn idk() -> Result<FwFile, std::io::Error> {
     let mut data = Vec::<u8>::new();
     match decrypt(&mut ifile, &mut data, &key, &iv) {
         Err(_) => panic!("could not decrypt"),
-        Ok(_) => println!("xx"),
+        Ok(_) => debug!("decrypted {} bytes", data.len()),
     }
 
     let mut ofile = File::create("fw_de.bin")?;// {
@@ -182,7 +188,7 @@ pub fn idk() -> Result<FwFile, std::io::Error> {
     let fw_file = decode_fw_file(data.as_slice());
 
     if let Some(fw) = fw_file {
-        println!("decoded header as: {:#?}", fw.header);
+        info!("decoded header as: {:#?}", fw.header);
         return Ok(fw);
     }
         
@@ -200,7 +206,7 @@ impl FwFile {
         self.data.to_vec()
     }
     pub fn header(&self) {
-        println!("decoded header as: {:#?}", self.header);
+        info!("decoded header as: {:#?}", self.header);
     }
 }
 
@@ -210,19 +216,19 @@ fn decode_fw_file(data: &[u8]) -> Option<FwFile> {
     let mut pos: usize = 0;
     for b in data.iter() {
         if *b == 35 && pos == 0 {
-            println!("found header start byte.. pos={} continuing", pos);
+            trace!("found header start byte.. pos={} continuing", pos);
             pos = pos+1;
             continue;
         }
         if *b == 35 && pos != 0 {
-            println!("found header end byte.. pos={} breaking", pos);
+            trace!("found header end byte.. pos={} breaking", pos);
             break;
         }
         buf.push(*b);
         pos = pos+1;
     }
     if buf.len() < 2 {
-        println!("no valid header found!");
+        warn!("no valid header found!");
         return None;
     }
     let hbuf: Vec<&str> = str::from_utf8(&buf).unwrap().split(|c| c == ';').collect();
@@ -279,7 +285,7 @@ fn decode_fw_image_header(v: &[&str]) -> Option<Header> {
         let s = v[0].to_string();
 
         if !validate_fw_image_header(&s) {
-            println!("unknown image type");
+            warn!("unknown image type");
             return None;
         }
         return Some(Header{
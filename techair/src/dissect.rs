@@ -0,0 +1,227 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+
+// Offline decoding of frames, for reading USB traces without an ACU.
+//
+// Bytes go through the same Framer and codec as a live session, so what is
+// printed is exactly what the library would have made of them. A frame whose
+// CRC16 is off still gets its payload decoded, it is flagged instead; traces
+// copied out by hand tend to have the odd typo.
+
+use std::fmt;
+use std::time::Duration;
+
+use crate::capture::CaptureEntry;
+use crate::cmd::meta::lookup;
+use techair_core::DecodeError;
+use crate::frame::{Direction, Frame};
+use crate::framing::Framer;
+use crate::usbcmd::UsbCmd;
+
+
+/// Reads hex such as "03 08 06 06 02", "0308", "0x03,0x08" or "03:08".
+pub fn parse_hex(s: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    for word in s.split(|c: char| c.is_whitespace() || c == ',' || c == ':') {
+        let digits = word.trim_start_matches("0x").trim_start_matches("0X");
+        if digits.len() % 2 != 0 {
+            return Err(format!("odd number of hex digits in '{}'", word));
+        }
+        for i in (0..digits.len()).step_by(2) {
+            let b = digits.get(i..(i + 2))
+                .and_then(|d| u8::from_str_radix(d, 16).ok())
+                .ok_or_else(|| format!("'{}' is not hex", word))?;
+            bytes.push(b);
+        }
+    }
+    Ok(bytes)
+}
+
+#[derive(Debug)]
+pub struct Dissection {
+    pub at: Option<Duration>,
+    pub dir: Direction,
+    pub frame: Vec<u8>,
+    /// The CRC16 the frame should carry, when it carries another one.
+    pub bad_crc: Option<u16>,
+    pub decoded: Result<UsbCmd, DecodeError>,
+}
+
+impl Dissection {
+    pub fn new(dir: Direction, frame: Vec<u8>) -> Dissection {
+        let mut bad_crc = None;
+        let decoded = match Frame::decode(dir, &frame) {
+            // group, subcommand and the CRC16, anything shorter has no
+            // subcommand left once the CRC is patched in.
+            Err(DecodeError::CrcMismatch{ expected, .. }) if frame.len() >= 4 => {
+                bad_crc = Some(expected);
+                let mut fixed = frame[..frame.len() - 2].to_vec();
+                fixed.extend_from_slice(&expected.to_le_bytes());
+                Frame::decode(dir, &fixed)
+            },
+            r => r,
+        };
+        Dissection{
+            at: None,
+            dir,
+            frame,
+            bad_crc,
+            decoded: decoded.map(|f| f.cmd),
+        }
+    }
+
+    pub fn crc_ok(&self) -> bool {
+        self.bad_crc.is_none() && self.frame.len() >= 4
+    }
+}
+
+impl fmt::Display for Dissection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(at) = self.at {
+            write!(f, "{}.{:06} ", at.as_secs(), at.subsec_micros())?;
+        }
+        let arrow = match self.dir {
+            Direction::Request  => '>',
+            Direction::Response => '<',
+        };
+        write!(f, "{}", arrow)?;
+        for b in &self.frame {
+            write!(f, " {:02x}", b)?;
+        }
+        writeln!(f)?;
+
+        if self.frame.len() >= 2 {
+            let (group, sub) = (self.frame[0], self.frame[1]);
+            write!(f, "    {:02x}/{:02x} {}/{}", group, sub,
+                   UsbCmd::group_name(group).unwrap_or("?"),
+                   lookup(group, sub).map_or("?", |c| c.name))?;
+        } else {
+            write!(f, "    ")?;
+        }
+        match self.bad_crc {
+            _ if self.frame.len() < 4 => writeln!(f, ", too short for a CRC16")?,
+            None => writeln!(f, ", crc ok")?,
+            Some(crc) => {
+                let n = self.frame.len();
+                let got = u16::from_le_bytes([self.frame[n - 2], self.frame[n - 1]]);
+                writeln!(f, ", crc BAD (got {:#06x}, expected {:#06x})", got, crc)?;
+            },
+        }
+        match &self.decoded {
+            Ok(cmd) => write!(f, "    {:?}", cmd),
+            Err(e) => write!(f, "    {}", e),
+        }
+    }
+}
+
+/// Splits `bytes` into frames travelling in `dir` and decodes each of them.
+/// Anything left over at the end comes back as a last, undecodable frame.
+pub fn dissect_bytes(dir: Direction, bytes: &[u8]) -> Vec<Dissection> {
+    let mut framer = Framer::new(dir);
+    framer.push(bytes);
+    let mut frames = Vec::new();
+    while let Some(frame) = framer.next_frame() {
+        frames.push(Dissection::new(dir, frame));
+    }
+    if !framer.is_empty() {
+        frames.push(Dissection::new(dir, framer.take_remaining()));
+    }
+    frames
+}
+
+/// Decodes a capture, each frame the way it was travelling.
+pub fn dissect_capture(entries: &[CaptureEntry]) -> Vec<Dissection> {
+    entries.iter()
+        .map(|e| Dissection{ at: Some(e.at), ..Dissection::new(e.dir, e.frame.clone()) })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::read_capture;
+    use crate::cmd::prelude::*;
+
+    #[test]
+    fn hex_spellings() {
+        let want = vec![0x03, 0x08, 0xab];
+        assert_eq!(parse_hex("03 08 ab").unwrap(), want);
+        assert_eq!(parse_hex(" 0308AB\n").unwrap(), want);
+        assert_eq!(parse_hex("0x03,0x08, 0xab").unwrap(), want);
+        assert_eq!(parse_hex("03:08:ab").unwrap(), want);
+        assert!(parse_hex("030").is_err());
+        assert!(parse_hex("03 zz").is_err());
+    }
+
+    #[test]
+    fn charging_state_reply() {
+        let mut frame = vec![0x03, 0x08, 0x05];
+        let crc = CRC16::calculate(&frame).as_u16();
+        frame.extend_from_slice(&crc.to_le_bytes());
+
+        let d = dissect_bytes(Direction::Response, &frame);
+        assert_eq!(d.len(), 1);
+        assert!(d[0].crc_ok());
+        match d[0].decoded {
+            Ok(UsbCmd::Measure(MeasureCmd::GetChargingState(Some(_)))) => {},
+            ref r => panic!("{:?}", r),
+        }
+        let text = d[0].to_string();
+        assert!(text.contains("03/08 Measure/GetChargingState, crc ok"), "{}", text);
+    }
+
+    #[test]
+    fn bad_crc_still_decodes() {
+        let d = dissect_bytes(Direction::Request, &[0x00, 0x02, 0x80, 0x72]);
+        assert_eq!(d.len(), 1);
+        assert_eq!(d[0].bad_crc, Some(0x7180));
+        match d[0].decoded {
+            Ok(UsbCmd::General(GeneralCmd::GetSoftwareVersion(None))) => {},
+            ref r => panic!("{:?}", r),
+        }
+        assert!(d[0].to_string().contains("crc BAD (got 0x7280, expected 0x7180)"));
+    }
+    #[test]
+    fn short_frame_with_bad_crc() {
+        let d = Dissection::new(Direction::Response, vec![0x03, 0x08, 0x01]);
+        assert_eq!(d.bad_crc, None);
+        assert!(matches!(d.decoded, Err(DecodeError::CrcMismatch{ .. })), "{:?}", d.decoded);
+        let s = d.to_string();
+        assert!(s.contains("03/08 Measure/GetChargingState, too short for a CRC16"), "{}", s);
+    }
+
+    #[test]
+    fn stream_of_frames() {
+        let bytes = parse_hex("00 02 80 71  00 04 00 73  00").unwrap();
+        let d = dissect_bytes(Direction::Request, &bytes);
+        assert_eq!(d.len(), 3);
+        assert!(d[0].decoded.is_ok());
+        assert!(d[1].decoded.is_ok());
+        assert_eq!(d[2].frame, [0x00]);
+        assert!(d[2].decoded.is_err());
+        assert!(d[2].to_string().contains("too short"));
+    }
+
+    #[test]
+    fn capture_keeps_direction_and_time() {
+        let text = "# techair capture v1\n0.000412 > 00 02 80 71\n";
+        let entries = read_capture(text.as_bytes()).unwrap();
+        let d = dissect_capture(&entries);
+        assert_eq!(d[0].at, Some(Duration::from_micros(412)));
+        assert!(d[0].to_string().starts_with("0.000412 > 00 02 80 71\n"));
+    }
+}
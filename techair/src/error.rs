@@ -0,0 +1,176 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+extern crate serialport;
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use techair_core::DecodeError;
+
+use crate::cmd::meta::Safety;
+
+
+/// Everything that can go wrong talking to an ACU.
+///
+/// Roughly split into the link failing (`Transport`, `Timeout`, `Port`),
+/// the frame being damaged on the way (`CrcMismatch`, `Truncated`) and the
+/// device saying something we do not understand, did not ask for or
+/// refused (`Rejected`). The
+/// session itself refuses some commands (`Refused`, `Unsupported`) and
+/// values (`InvalidArgument`).
+#[derive(Debug)]
+pub enum TechAirError {
+    Transport(io::Error),
+    Timeout,
+    Port(serialport::Error),
+    NotFound(String),
+    CrcMismatch{ expected: u16, got: u16 },
+    Truncated{ needed: usize, got: usize },
+    UnknownGroup(u8),
+    MissingSubcommand(u8),
+    UnknownSubcommand{ group: u8, sub: u8 },
+    ShortPayload{ group: u8, sub: u8, needed: usize, got: usize },
+    InvalidPayload{ group: u8, sub: u8 },
+    UnexpectedResponse{ sent: (u8, u8), received: (u8, u8) },
+    Firmware(String),
+    // the ACU answered a setter with a non-zero status.
+    Rejected{ group: u8, sub: u8, status: u8 },
+    // the command is above the safety limit set on the session.
+    Refused{ group: u8, sub: u8, safety: Safety },
+    // the ACU firmware is too old to know the command.
+    Unsupported{ group: u8, sub: u8, min_firmware: u16, firmware: u16 },
+    // a value the ACU cannot take, caught before sending it.
+    InvalidArgument(String),
+}
+
+impl TechAirError {
+    /// True when the link itself misbehaved, as opposed to the ACU
+    /// answering with something we cannot make sense of.
+    pub fn is_link_error(&self) -> bool {
+        matches!(self,
+            TechAirError::Transport(_) |
+            TechAirError::Timeout |
+            TechAirError::Port(_) |
+            TechAirError::NotFound(_) |
+            TechAirError::CrcMismatch{ .. } |
+            TechAirError::Truncated{ .. })
+    }
+}
+
+impl fmt::Display for TechAirError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TechAirError::Transport(e) => write!(f, "transport error: {}", e),
+            TechAirError::Timeout => write!(f, "timed out waiting for the ACU"),
+            TechAirError::Port(e) => write!(f, "serial port error: {}", e),
+            TechAirError::NotFound(what) => write!(f, "{}", what),
+            TechAirError::CrcMismatch{ expected, got } =>
+                write!(f, "CRC16 mismatch, expected {:#06x} got {:#06x}", expected, got),
+            TechAirError::Truncated{ needed, got } =>
+                write!(f, "truncated frame, needed {} bytes got {}", needed, got),
+            TechAirError::UnknownGroup(group) => write!(f, "unknown command group {:#04x}", group),
+            TechAirError::MissingSubcommand(group) =>
+                write!(f, "no subcommand byte for group {:#04x}", group),
+            TechAirError::UnknownSubcommand{ group, sub } =>
+                write!(f, "unknown subcommand {:#04x} in group {:#04x}", sub, group),
+            TechAirError::ShortPayload{ group, sub, needed, got } =>
+                write!(f, "short payload for {:#04x}/{:#04x}, needed {} bytes got {}",
+                       group, sub, needed, got),
+            TechAirError::InvalidPayload{ group, sub } =>
+                write!(f, "could not decode the payload of {:#04x}/{:#04x}", group, sub),
+            TechAirError::UnexpectedResponse{ sent, received } =>
+                write!(f, "sent {:#04x}/{:#04x} but the ACU answered {:#04x}/{:#04x}",
+                       sent.0, sent.1, received.0, received.1),
+            TechAirError::Firmware(why) => write!(f, "firmware update failed: {}", why),
+            TechAirError::Rejected{ group, sub, status } =>
+                write!(f, "the ACU rejected {:#04x}/{:#04x} with status {:#04x}", group, sub, status),
+            TechAirError::Refused{ group, sub, safety } =>
+                write!(f, "refusing to send {:#04x}/{:#04x}, it is a {} command",
+                       group, sub, safety),
+            TechAirError::Unsupported{ group, sub, min_firmware, firmware } =>
+                write!(f, "{:#04x}/{:#04x} needs ACU firmware {} or newer, this one is {}",
+                       group, sub, min_firmware, firmware),
+            TechAirError::InvalidArgument(why) => write!(f, "invalid argument: {}", why),
+        }
+    }
+}
+
+impl Error for TechAirError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TechAirError::Transport(e) => Some(e),
+            TechAirError::Port(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for TechAirError {
+    fn from(err: io::Error) -> TechAirError {
+        match err.kind() {
+            io::ErrorKind::TimedOut => TechAirError::Timeout,
+            _ => TechAirError::Transport(err),
+        }
+    }
+}
+
+impl From<DecodeError> for TechAirError {
+    fn from(err: DecodeError) -> TechAirError {
+        match err {
+            DecodeError::CrcMismatch{ expected, got } => TechAirError::CrcMismatch{ expected, got },
+            DecodeError::Truncated{ needed, got } => TechAirError::Truncated{ needed, got },
+            DecodeError::UnknownGroup(group) => TechAirError::UnknownGroup(group),
+            DecodeError::MissingSubcommand(group) => TechAirError::MissingSubcommand(group),
+            DecodeError::UnknownSubcommand{ group, sub } => TechAirError::UnknownSubcommand{ group, sub },
+            DecodeError::ShortPayload{ group, sub, needed, got } =>
+                TechAirError::ShortPayload{ group, sub, needed, got },
+            DecodeError::InvalidPayload{ group, sub } => TechAirError::InvalidPayload{ group, sub },
+            DecodeError::Firmware(why) => TechAirError::Firmware(why.to_string()),
+        }
+    }
+}
+
+impl From<serialport::Error> for TechAirError {
+    fn from(err: serialport::Error) -> TechAirError {
+        TechAirError::Port(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+    use crate::usbcmd::UsbCmd;
+
+    #[test]
+    fn decode_errors_convert() {
+        match UsbCmd::try_from(vec![0x00,0x02,0x01,0x17,0x00,0x00]).map_err(TechAirError::from) {
+            Err(TechAirError::CrcMismatch{ got: 0, .. }) => {},
+            r => panic!("{:?}", r),
+        }
+        let err = TechAirError::from(DecodeError::Firmware("xfer hex data failed"));
+        assert_eq!(err.to_string(), "firmware update failed: xfer hex data failed");
+    }
+    #[test]
+    fn timeouts_are_link_errors() {
+        let err = TechAirError::from(io::Error::new(io::ErrorKind::TimedOut, "no bytes returned"));
+        assert!(matches!(err, TechAirError::Timeout));
+        assert!(err.is_link_error());
+        assert!(!TechAirError::UnknownGroup(0x42).is_link_error());
+    }
+}
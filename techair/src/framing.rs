@@ -0,0 +1,232 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+// Splits the byte stream coming off a Transport back into frames.
+//
+// Every frame is  group || subcmd || payload || CRC16 (LSB first), but
+// nothing on the wire says how long the payload is. Most commands have a
+// fixed size reply though, so look that up first and fall back to scanning
+// for a position where the MODBUS CRC16 checks out.
+
+use std::io;
+
+use crate::cmd::prelude::*;
+use crate::cmd::meta::{PayloadLen, lookup};
+use crate::transport::Transport;
+
+
+pub use crate::frame::Direction;
+
+fn response_payload_len(group: u8, subcmd: u8) -> PayloadLen {
+    lookup(group, subcmd).map_or(PayloadLen::Unknown, |c| c.response)
+}
+
+fn request_payload_len(group: u8, subcmd: u8) -> PayloadLen {
+    lookup(group, subcmd).map_or(PayloadLen::Fixed(0), |c| c.request)
+}
+
+enum Expect {
+    Len(usize),
+    NeedMore,
+    Unknown,
+}
+
+fn crc_ok(frame: &[u8]) -> bool {
+    if frame.len() < 4 {
+        return false;
+    }
+    let (data, crc) = frame.split_at(frame.len() - 2);
+    CRC16::calculate(data).as_u16() == ((crc[1] as u16) << 8 | crc[0] as u16)
+}
+
+pub struct Framer {
+    dir: Direction,
+    buf: Vec<u8>,
+}
+
+impl Framer {
+    pub fn new(dir: Direction) -> Framer {
+        Framer{
+            dir,
+            buf: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.buf.clear();
+    }
+
+    // hands back whatever is buffered, complete frame or not.
+    pub fn take_remaining(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.buf)
+    }
+
+    fn expect(&self) -> Expect {
+        if self.buf.len() < 2 {
+            return Expect::NeedMore;
+        }
+        let len = match self.dir {
+            Direction::Request => request_payload_len(self.buf[0], self.buf[1]),
+            Direction::Response => response_payload_len(self.buf[0], self.buf[1]),
+        };
+        match len {
+            PayloadLen::Fixed(n) => Expect::Len(2 + n + 2),
+            PayloadLen::Counted{ skip, width } => {
+                let at = 2 + skip;
+                if self.buf.len() < at + width {
+                    return Expect::NeedMore;
+                }
+                let count = self.buf[at..(at + width)].iter()
+                    .fold(0usize, |acc, b| (acc << 8) | *b as usize);
+                Expect::Len(at + width + count + 2)
+            },
+            PayloadLen::Unknown => Expect::Unknown,
+        }
+    }
+
+    /// Splits the next complete frame off the buffer, if there is one.
+    pub fn next_frame(&mut self) -> Option<Vec<u8>> {
+        let start = match self.expect() {
+            Expect::NeedMore => return None,
+            Expect::Len(n) => {
+                if self.buf.len() < n {
+                    return None;
+                }
+                if crc_ok(&self.buf[..n]) {
+                    return Some(self.buf.drain(..n).collect());
+                }
+                // longer than we thought, keep looking further on.
+                n + 1
+            },
+            Expect::Unknown => 4,
+        };
+        let n = (start..=self.buf.len()).find(|&n| crc_ok(&self.buf[..n]))?;
+        Some(self.buf.drain(..n).collect())
+    }
+
+    /// Reads from `link` until a complete frame is in. Should the link go
+    /// quiet first, whatever arrived is returned as is and left for the
+    /// decoder to reject.
+    pub fn read_frame<T: Transport + ?Sized>(&mut self, link: &mut T) -> io::Result<Vec<u8>> {
+        let mut chunk = [0u8; 256];
+        loop {
+            if let Some(frame) = self.next_frame() {
+                return Ok(frame);
+            }
+            match link.read_bytes(&mut chunk) {
+                Ok(0) => {},
+                Ok(n) => {
+                    self.push(&chunk[..n]);
+                    continue;
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => {},
+                Err(e) => return Err(e),
+            }
+            if self.is_empty() {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "no bytes returned"));
+            }
+            return Ok(self.take_remaining());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::{TechAirEncoder, TechAirResponseEncoder};
+    use crate::usbcmd::UsbCmd;
+    use proptest::prelude::*;
+    use std::convert::TryFrom;
+
+    fn response(cmd: UsbCmd) -> Vec<u8> {
+        let mut buf = Vec::new();
+        cmd.write_response_bytes(&mut buf);
+        buf
+    }
+
+    #[test]
+    fn fixed_len_response() {
+        let frame = response(UsbCmd::Logging(LoggingCmd::GetBatCount(Some(7))));
+        let mut f = Framer::new(Direction::Response);
+        f.push(&frame[..3]);
+        assert_eq!(f.next_frame(), None);
+        f.push(&frame[3..]);
+        assert_eq!(f.next_frame(), Some(frame));
+        assert!(f.is_empty());
+    }
+    #[test]
+    fn counted_response() {
+        let frame = response(UsbCmd::General(GeneralCmd::GetCustomerInfo(Some("Rider".to_string()))));
+        let mut f = Framer::new(Direction::Response);
+        f.push(&frame[..frame.len() - 1]);
+        assert_eq!(f.next_frame(), None);
+        f.push(&frame[frame.len() - 1..]);
+        assert_eq!(f.next_frame(), Some(frame));
+    }
+    #[test]
+    fn scanned_response() {
+        let frame = response(UsbCmd::General(GeneralCmd::GetSerialNr(Some("TA0000001".to_string()))));
+        let mut f = Framer::new(Direction::Response);
+        f.push(&frame);
+        assert_eq!(f.next_frame(), Some(frame));
+    }
+    #[test]
+    fn concatenated_responses() {
+        let a = response(UsbCmd::Measure(MeasureCmd::GetLogicVoltage(3.3)));
+        let b = response(UsbCmd::General(GeneralCmd::GetSerialNr(Some("TA01".to_string()))));
+        let mut f = Framer::new(Direction::Response);
+        f.push(&a);
+        f.push(&b);
+        assert_eq!(f.next_frame(), Some(a));
+        assert_eq!(f.next_frame(), Some(b));
+        assert_eq!(f.next_frame(), None);
+    }
+    #[test]
+    fn fw_data_request() {
+        let chunk = [0xa5u8; 256];
+        let fw_data = FWData::new(&chunk, &3).unwrap();
+        let mut frame = Vec::new();
+        UsbCmd::SWUpdate(SWUpdateCmd::WriteFWData(fw_data)).write_bytes(&mut frame);
+        let mut f = Framer::new(Direction::Request);
+        f.push(&frame[..100]);
+        assert_eq!(f.next_frame(), None);
+        f.push(&frame[100..]);
+        assert_eq!(f.next_frame(), Some(frame));
+    }
+
+    proptest! {
+        #[test]
+        fn framer_never_panics(v in proptest::collection::vec(any::<u8>(), 0..128)) {
+            for dir in [Direction::Request, Direction::Response].iter() {
+                let mut f = Framer::new(*dir);
+                f.push(&v);
+                while let Some(frame) = f.next_frame() {
+                    let _ = UsbCmd::try_from(frame);
+                }
+                f.take_remaining();
+            }
+        }
+    }
+}
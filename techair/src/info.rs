@@ -0,0 +1,303 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+
+// A snapshot of everything an ACU tells without being changed.
+//
+// Collected over one session so an intake script gets the whole unit in
+// a single invocation. A query the unit does not answer is noted in the
+// report rather than failing it, only the software version must be there.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::cmd::prelude::*;
+use crate::error::TechAirError;
+use crate::session::{Acu, SensorRevisionType};
+use crate::transport::Transport;
+
+
+/// Calibrated resistance of both squibs in Ohms, `None` when out of range.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct SquibRes {
+    pub squib_1: Option<f32>,
+    pub squib_2: Option<f32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct SensorRevision {
+    pub software: f32,
+    pub hardware: f32,
+}
+
+impl fmt::Display for SensorRevision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "sw {}, hw {}", self.software, self.hardware)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct SensorRevisions {
+    pub right_hand: Option<SensorRevision>,
+    pub left_hand: Option<SensorRevision>,
+    pub right_foot: Option<SensorRevision>,
+    pub left_foot: Option<SensorRevision>,
+}
+
+/// What `techair-cli info` reports, `None` where the ACU did not answer.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct DeviceInfo {
+    /// "3.05", as the firmware numbers it.
+    pub sw_version: String,
+    pub hw_version: Option<HardwareVersion>,
+    pub bootloader_version: Option<u8>,
+    pub serial_nr: Option<String>,
+    pub customer_info: Option<String>,
+    /// Also `None` when the unit was never serviced.
+    pub service_date: Option<ServiceDate>,
+    pub operating_modus: Option<OpModus>,
+    pub inflation_type: Option<String>,
+    /// "hh:mm:ss".
+    pub op_hours: Option<String>,
+    pub bat_count: Option<u16>,
+    pub errors: Option<u8>,
+    pub precrash_logs: Option<u16>,
+    pub postcrash_logs: Option<u16>,
+    pub squib_res: Option<SquibRes>,
+    pub sensor_revisions: SensorRevisions,
+    /// Why each missing field is missing.
+    pub unavailable: BTreeMap<&'static str, String>,
+}
+
+/// The name the vendor tool uses, or the raw byte.
+pub fn inflation_type_name(it: u8) -> String {
+    match it {
+        0x44 => "single".to_string(),
+        0xb4 => "double-race".to_string(),
+        0xbb => "double".to_string(),
+        0xff => "clear".to_string(),
+        _    => format!("{:#04x}", it),
+    }
+}
+
+impl DeviceInfo {
+    /// Runs every read-only query over `acu`.
+    ///
+    /// Nothing is written, so sensor revisions are only read when sensor
+    /// readings are already enabled and unavailable otherwise.
+    /// The bootloader version is asked for without starting the bootloader,
+    /// a unit that only answers it from there reports it as unavailable.
+    pub fn collect<T: Transport>(acu: &mut Acu<T>) -> Result<DeviceInfo, TechAirError> {
+        let fw = acu.firmware()?;
+        let mut info = DeviceInfo{
+            sw_version: format!("{}.{:02}", fw / 100, fw % 100),
+            ..DeviceInfo::default()
+        };
+        let missing = &mut info.unavailable;
+
+        info.hw_version = note(missing, "hw_version", acu.hw_version());
+        info.bootloader_version = note(missing, "bootloader_version", acu.bootloader_version());
+        info.serial_nr = note(missing, "serial_nr", acu.serial_nr());
+        info.customer_info = note(missing, "customer_info", acu.customer_info());
+        info.service_date = note(missing, "service_date", acu.service_date()).flatten();
+        info.operating_modus = note(missing, "operating_modus", acu.operating_modus());
+        info.inflation_type = note(missing, "inflation_type", acu.inflation_type())
+            .map(inflation_type_name);
+        info.op_hours = note(missing, "op_hours", acu.op_hours());
+        info.bat_count = note(missing, "bat_count", acu.bat_count());
+        info.errors = note(missing, "errors", acu.no_of_errors());
+        info.precrash_logs = note(missing, "precrash_logs", acu.no_of_precrash_logs());
+        info.postcrash_logs = note(missing, "postcrash_logs", acu.no_of_postcrash_logs());
+        info.squib_res = note(missing, "squib_res", acu.calib_squib_res())
+            .map(|(squib_1, squib_2)| SquibRes{ squib_1, squib_2 });
+
+        // enabling them would change the unit, so only read what is on.
+        let revs = &mut info.sensor_revisions;
+        match acu.sensor_reading_enables() {
+            Ok(0) => {
+                missing.insert("sensor_revisions", "sensor readings are not enabled".to_string());
+            },
+            Ok(_) => {
+                let sensors = [
+                    ("right_hand", SensorRevisionType::RightHand, &mut revs.right_hand),
+                    ("left_hand", SensorRevisionType::LeftHand, &mut revs.left_hand),
+                    ("right_foot", SensorRevisionType::RightFoot, &mut revs.right_foot),
+                    ("left_foot", SensorRevisionType::LeftFoot, &mut revs.left_foot),
+                ];
+                for (name, srt, rev) in sensors {
+                    *rev = note(missing, name, acu.sensor_revision(srt))
+                        .map(|(software, hardware)| SensorRevision{ software, hardware });
+                }
+            },
+            Err(e) => {
+                missing.insert("sensor_revisions", e.to_string());
+            },
+        }
+        Ok(info)
+    }
+}
+
+// the value, or None with the reason kept under `field`.
+fn note<V>(missing: &mut BTreeMap<&'static str, String>, field: &'static str,
+           r: Result<V, TechAirError>) -> Option<V> {
+    match r {
+        Ok(v) => Some(v),
+        Err(e) => {
+            debug!("info: no {}: {}", field, e);
+            missing.insert(field, e.to_string());
+            None
+        },
+    }
+}
+
+// a missing field reads "-".
+struct Or<'a, V>(&'a Option<V>);
+
+impl<V: fmt::Display> fmt::Display for Or<'_, V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(v) => v.fmt(f),
+            None    => write!(f, "-"),
+        }
+    }
+}
+
+impl fmt::Display for DeviceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "sw version:         {}", self.sw_version)?;
+        writeln!(f, "hw version:         {}", Or(&self.hw_version))?;
+        writeln!(f, "bootloader version: {}", Or(&self.bootloader_version))?;
+        writeln!(f, "serial nr:          {}", Or(&self.serial_nr))?;
+        writeln!(f, "customer info:      {}", Or(&self.customer_info))?;
+        match self.service_date {
+            None if !self.unavailable.contains_key("service_date") =>
+                writeln!(f, "service date:       never")?,
+            _ => writeln!(f, "service date:       {}", Or(&self.service_date))?,
+        }
+        writeln!(f, "operating modus:    {}", Or(&self.operating_modus))?;
+        writeln!(f, "inflation type:     {}", Or(&self.inflation_type))?;
+        writeln!(f, "op hours:           {}", Or(&self.op_hours))?;
+        writeln!(f, "bat count:          {}", Or(&self.bat_count))?;
+        writeln!(f, "errors:             {}", Or(&self.errors))?;
+        writeln!(f, "pre-crash logs:     {}", Or(&self.precrash_logs))?;
+        writeln!(f, "post-crash logs:    {}", Or(&self.postcrash_logs))?;
+        match self.squib_res {
+            Some(r) => writeln!(f, "squib resistance:   {} / {} Ohms", Or(&r.squib_1), Or(&r.squib_2))?,
+            None    => writeln!(f, "squib resistance:   -")?,
+        }
+        let revs = &self.sensor_revisions;
+        writeln!(f, "right hand sensor:  {}", Or(&revs.right_hand))?;
+        writeln!(f, "left hand sensor:   {}", Or(&revs.left_hand))?;
+        writeln!(f, "right foot sensor:  {}", Or(&revs.right_foot))?;
+        write!(f, "left foot sensor:   {}", Or(&revs.left_foot))?;
+        for (field, why) in &self.unavailable {
+            write!(f, "\n  no {}: {}", field, why)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use crate::cmd::meta::{CommandMeta, Safety};
+    use crate::frame::{Direction, Frame};
+    use crate::framing::Framer;
+    use crate::sim::{SimConfig, Simulator};
+    use crate::transport::pipe;
+
+    fn simulated() -> Acu<crate::transport::PipeTransport> {
+        let (host, mut device) = pipe();
+        thread::spawn(move || {
+            let mut sim = Simulator::new(SimConfig::default());
+            let _ = sim.serve(&mut device);
+        });
+        Acu::with_transport(host)
+    }
+
+    #[test]
+    fn collects_everything() {
+        let cfg = SimConfig::default();
+        let info = DeviceInfo::collect(&mut simulated()).unwrap();
+        assert!(info.unavailable.is_empty(), "{:?}", info.unavailable);
+        assert_eq!(info.sw_version, "3.05");
+        assert_eq!(info.hw_version, Some(HardwareVersion::from_raw(100)));
+        assert_eq!(info.bootloader_version, Some(cfg.bootloader.version));
+        assert_eq!(info.serial_nr.as_deref(), Some("TA0000001"));
+        assert_eq!(info.service_date, Some("01/01/2020".parse().unwrap()));
+        assert_eq!(info.operating_modus, Some(OpModus::from_raw(0xaa, 0x00)));
+        assert_eq!(info.inflation_type.as_deref(), Some("double"));
+        assert_eq!(info.bat_count, Some(cfg.logs.bat_count));
+        assert_eq!(info.squib_res, Some(SquibRes{ squib_1: Some(2.1), squib_2: Some(2.1) }));
+        let (software, hardware) = cfg.sensors.left_foot_rev;
+        assert_eq!(info.sensor_revisions.left_foot, Some(SensorRevision{ software, hardware }));
+        assert!(info.to_string().contains("\nserial nr:          TA0000001\n"));
+    }
+    #[test]
+    fn sends_nothing_but_reads() {
+        let (host, mut device) = pipe();
+        let sim = thread::spawn(move || {
+            let mut sim = Simulator::new(SimConfig::default());
+            let mut framer = Framer::new(Direction::Request);
+            let mut sent = Vec::new();
+            // the host has gone once nothing arrives for a while.
+            while let Ok(req) = framer.read_frame(&mut device) {
+                sent.push(Frame::decode(Direction::Request, &req).unwrap().cmd);
+                device.write_frame(&sim.handle(&req).unwrap()).unwrap();
+            }
+            sent
+        });
+        let mut acu = Acu::with_transport(host);
+        acu.techair().set_safety_limit(Safety::ReadOnly);
+        let info = DeviceInfo::collect(&mut acu).unwrap();
+        drop(acu);
+        assert!(info.unavailable.is_empty(), "{:?}", info.unavailable);
+        let sent = sim.join().unwrap();
+        assert!(!sent.is_empty());
+        for cmd in sent {
+            assert!(cmd.safety() <= Safety::ReadOnly, "{:?} is {}", cmd, cmd.safety());
+        }
+    }
+    #[test]
+    fn sensors_left_off() {
+        let mut cfg = SimConfig::default();
+        cfg.sensors.enables = 0x00;
+        let (host, mut device) = pipe();
+        thread::spawn(move || {
+            let _ = Simulator::new(cfg).serve(&mut device);
+        });
+        let info = DeviceInfo::collect(&mut Acu::with_transport(host)).unwrap();
+        assert_eq!(info.sensor_revisions, SensorRevisions::default());
+        assert_eq!(info.unavailable.keys().copied().collect::<Vec<_>>(), ["sensor_revisions"]);
+        assert!(info.serial_nr.is_some());
+        assert!(info.to_string().contains("\nright hand sensor:  -\n"));
+    }
+    #[test]
+    fn needs_an_answer() {
+        let (host, _device) = pipe();
+        let mut acu = Acu::with_transport(host);
+        acu.set_retry_policy(crate::retry::RetryPolicy::never());
+        assert!(DeviceInfo::collect(&mut acu).is_err());
+    }
+    #[test]
+    fn inflation_types() {
+        assert_eq!(inflation_type_name(0x44), "single");
+        assert_eq!(inflation_type_name(0x12), "0x12");
+    }
+}
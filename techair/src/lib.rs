@@ -14,14 +14,27 @@
 // along with this program; if not, write to the Free Software
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
 
+#[macro_use]
+extern crate log;
+
 pub mod cli;
-pub mod usbcmd;
+pub mod error;
 pub mod serial;
-pub mod encoder;
+pub mod transport;
+pub mod framing;
+pub mod capture;
+pub mod dissect;
+pub mod usbmon;
+pub mod session;
+pub mod service;
+pub mod info;
+pub mod query;
+pub mod retry;
+pub mod sim;
 pub mod crypto;
 
-// export the pub interface to cmd/mod.rs
-pub mod cmd;
+// the codec itself lives in techair-core.
+pub use techair_core::{cmd, encoder, frame, usbcmd};
 
 pub use crate::cmd::prelude::*;
 pub use crate::usbcmd::UsbCmd;
@@ -0,0 +1,414 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+
+// Typed requests.
+//
+// `UsbCmd` is what goes over the wire, the same variant carries a
+// placeholder on the way out and the value on the way back. A `Query`
+// pairs a request with the type its reply decodes to, so e.g.
+// `acu.query(GetLogicVoltage)` hands back the volts as an `f32`.
+
+use crate::error::TechAirError;
+use crate::session::{MeasureVoltageType, SensorRevisionType};
+use crate::usbcmd::UsbCmd;
+use crate::cmd::prelude::*;
+
+
+/// A request together with what its reply decodes to.
+pub trait Query {
+    type Response;
+
+    /// The frame to send.
+    fn request(&self) -> UsbCmd;
+
+    /// Picks the value out of the reply, which is handed back as is when
+    /// it is not the one for this request.
+    fn response(&self, reply: UsbCmd) -> Result<Self::Response, UsbCmd>;
+}
+
+// the ids were already matched to the request, so the payload was not
+// what the request expects.
+pub(crate) fn invalid(reply: UsbCmd) -> TechAirError {
+    let (group, sub) = reply.ids();
+    TechAirError::InvalidPayload{ group, sub }
+}
+
+macro_rules! query {
+    ($(#[$doc:meta])* $name:ident -> $resp:ty,
+     $req:expr, $reply:pat => $val:expr) => {
+        $(#[$doc])*
+        #[derive(Clone, Copy, Debug, Default, PartialEq)]
+        pub struct $name;
+
+        impl Query for $name {
+            type Response = $resp;
+
+            fn request(&self) -> UsbCmd {
+                $req
+            }
+            fn response(&self, reply: UsbCmd) -> Result<$resp, UsbCmd> {
+                match reply {
+                    $reply => Ok($val),
+                    r => Err(r),
+                }
+            }
+        }
+    };
+}
+
+// the General setters, answered with a status byte that is 0 when the
+// ACU took the value.
+macro_rules! setter {
+    ($name:ident($ty:ty)) => {
+        #[derive(Clone, Debug, PartialEq)]
+        pub struct $name(pub $ty);
+
+        impl Query for $name {
+            type Response = u8;
+
+            fn request(&self) -> UsbCmd {
+                UsbCmd::General(GeneralCmd::$name(Setting::Value(self.0.clone())))
+            }
+            fn response(&self, reply: UsbCmd) -> Result<u8, UsbCmd> {
+                match reply {
+                    UsbCmd::General(GeneralCmd::$name(Setting::Status(status))) => Ok(status),
+                    r => Err(r),
+                }
+            }
+        }
+    };
+}
+
+// General.
+
+query!(GetCtrlMode -> CtrlMode,
+       UsbCmd::General(GeneralCmd::GetCtrlMode(None)),
+       UsbCmd::General(GeneralCmd::GetCtrlMode(Some(mode))) => mode);
+query!(GetSoftwareVersion -> f32,
+       UsbCmd::General(GeneralCmd::GetSoftwareVersion(None)),
+       UsbCmd::General(GeneralCmd::GetSoftwareVersion(Some(ver))) => ver);
+query!(GetOperatingModus -> OpModus,
+       UsbCmd::General(GeneralCmd::GetOperatingModus(None)),
+       UsbCmd::General(GeneralCmd::GetOperatingModus(Some(modus))) => modus);
+query!(GetSerialNr -> String,
+       UsbCmd::General(GeneralCmd::GetSerialNr(None)),
+       UsbCmd::General(GeneralCmd::GetSerialNr(Some(nr))) => nr);
+query!(GetHardwareVersion -> HardwareVersion,
+       UsbCmd::General(GeneralCmd::GetHardwareVersion(None)),
+       UsbCmd::General(GeneralCmd::GetHardwareVersion(Some(ver))) => ver);
+query!(GetCustomerInfo -> String,
+       UsbCmd::General(GeneralCmd::GetCustomerInfo(None)),
+       UsbCmd::General(GeneralCmd::GetCustomerInfo(Some(info))) => info);
+query!(/// `None` when the unit was never serviced.
+       GetServiceDate -> Option<ServiceDate>,
+       UsbCmd::General(GeneralCmd::GetServiceDate(None)),
+       UsbCmd::General(GeneralCmd::GetServiceDate(date)) => date);
+
+setter!(SetCtrlMode(CtrlMode));
+setter!(SetSerialNr(String));
+setter!(SetHardwareVersion(HardwareVersion));
+setter!(SetCustomerInfo(String));
+setter!(SetServiceDate(ServiceDate));
+
+// Logging.
+
+query!(/// "hh:mm:ss".
+       GetOPHours -> String,
+       UsbCmd::Logging(LoggingCmd::GetOPHours(None)),
+       UsbCmd::Logging(LoggingCmd::GetOPHours(Some(hours))) => hours);
+query!(GetNumOfErrors -> u8,
+       UsbCmd::Logging(LoggingCmd::GetNumOfErrors(None)),
+       UsbCmd::Logging(LoggingCmd::GetNumOfErrors(Some(n))) => n);
+query!(GetNumOfPreCrashLogs -> u16,
+       UsbCmd::Logging(LoggingCmd::GetNumOfPreCrashLogs(None)),
+       UsbCmd::Logging(LoggingCmd::GetNumOfPreCrashLogs(Some(n))) => n);
+query!(GetPreCrashLogs -> Vec<u8>,
+       UsbCmd::Logging(LoggingCmd::GetPreCrashLogs(None)),
+       UsbCmd::Logging(LoggingCmd::GetPreCrashLogs(Some(logs))) => logs);
+query!(GetNumOfPostCrashLogs -> u16,
+       UsbCmd::Logging(LoggingCmd::GetNumOfPostCrashLogs(None)),
+       UsbCmd::Logging(LoggingCmd::GetNumOfPostCrashLogs(Some(n))) => n);
+query!(GetErrorHistory -> Vec<u8>,
+       UsbCmd::Logging(LoggingCmd::GetErrorHistory(None)),
+       UsbCmd::Logging(LoggingCmd::GetErrorHistory(Some(errors))) => errors);
+query!(GetPostCrashLogs -> Vec<u8>,
+       UsbCmd::Logging(LoggingCmd::GetPostCrashLogs(None)),
+       UsbCmd::Logging(LoggingCmd::GetPostCrashLogs(Some(logs))) => logs);
+query!(GetBatCount -> u16,
+       UsbCmd::Logging(LoggingCmd::GetBatCount(None)),
+       UsbCmd::Logging(LoggingCmd::GetBatCount(Some(n))) => n);
+
+// Measure.
+
+query!(/// Volts, as are the other rails.
+       GetLogicVoltage -> f32,
+       UsbCmd::Measure(MeasureCmd::GetLogicVoltage(0.00)),
+       UsbCmd::Measure(MeasureCmd::GetLogicVoltage(v)) => v);
+query!(GetPeripheralVoltage -> f32,
+       UsbCmd::Measure(MeasureCmd::GetPeripheralVoltage(0.00)),
+       UsbCmd::Measure(MeasureCmd::GetPeripheralVoltage(v)) => v);
+query!(GetRightHandVoltage -> f32,
+       UsbCmd::Measure(MeasureCmd::GetRightHandVoltage(0.00)),
+       UsbCmd::Measure(MeasureCmd::GetRightHandVoltage(v)) => v);
+query!(GetLeftHandVoltage -> f32,
+       UsbCmd::Measure(MeasureCmd::GetLeftHandVoltage(0.00)),
+       UsbCmd::Measure(MeasureCmd::GetLeftHandVoltage(v)) => v);
+query!(GetRightFootVoltage -> f32,
+       UsbCmd::Measure(MeasureCmd::GetRightFootVoltage(0.00)),
+       UsbCmd::Measure(MeasureCmd::GetRightFootVoltage(v)) => v);
+query!(GetLeftFootVoltage -> f32,
+       UsbCmd::Measure(MeasureCmd::GetLeftFootVoltage(0.00)),
+       UsbCmd::Measure(MeasureCmd::GetLeftFootVoltage(v)) => v);
+query!(GetSquibVoltage -> f32,
+       UsbCmd::Measure(MeasureCmd::GetSquibVoltage(0.00)),
+       UsbCmd::Measure(MeasureCmd::GetSquibVoltage(v)) => v);
+query!(GetBatteryVoltage -> f32,
+       UsbCmd::Measure(MeasureCmd::GetBatteryVoltage(0.00)),
+       UsbCmd::Measure(MeasureCmd::GetBatteryVoltage(v)) => v);
+query!(GetChargingState -> String,
+       UsbCmd::Measure(MeasureCmd::GetChargingState(None)),
+       UsbCmd::Measure(MeasureCmd::GetChargingState(Some(state))) => state);
+query!(/// True when the zip is closed.
+       GetZIPSwitchState -> bool,
+       UsbCmd::Measure(MeasureCmd::GetZIPSwitchState(false)),
+       UsbCmd::Measure(MeasureCmd::GetZIPSwitchState(closed)) => closed);
+
+/// Sets the LEDs, the reply echoes a status byte.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SetEXTDisplay(pub u8);
+
+impl Query for SetEXTDisplay {
+    type Response = u8;
+
+    fn request(&self) -> UsbCmd {
+        UsbCmd::Measure(MeasureCmd::SetEXTDisplay(self.0))
+    }
+    fn response(&self, reply: UsbCmd) -> Result<u8, UsbCmd> {
+        match reply {
+            UsbCmd::Measure(MeasureCmd::SetEXTDisplay(ret)) => Ok(ret),
+            r => Err(r),
+        }
+    }
+}
+
+impl Query for MeasureVoltageType {
+    type Response = f32;
+
+    fn request(&self) -> UsbCmd {
+        match self {
+            MeasureVoltageType::Logic      => GetLogicVoltage.request(),
+            MeasureVoltageType::Peripheral => GetPeripheralVoltage.request(),
+            MeasureVoltageType::RightHand  => GetRightHandVoltage.request(),
+            MeasureVoltageType::LeftHand   => GetLeftHandVoltage.request(),
+            MeasureVoltageType::RightFoot  => GetRightFootVoltage.request(),
+            MeasureVoltageType::LeftFoot   => GetLeftFootVoltage.request(),
+            MeasureVoltageType::Squib      => GetSquibVoltage.request(),
+            MeasureVoltageType::Battery    => GetBatteryVoltage.request(),
+        }
+    }
+    fn response(&self, reply: UsbCmd) -> Result<f32, UsbCmd> {
+        match self {
+            MeasureVoltageType::Logic      => GetLogicVoltage.response(reply),
+            MeasureVoltageType::Peripheral => GetPeripheralVoltage.response(reply),
+            MeasureVoltageType::RightHand  => GetRightHandVoltage.response(reply),
+            MeasureVoltageType::LeftHand   => GetLeftHandVoltage.response(reply),
+            MeasureVoltageType::RightFoot  => GetRightFootVoltage.response(reply),
+            MeasureVoltageType::LeftFoot   => GetLeftFootVoltage.response(reply),
+            MeasureVoltageType::Squib      => GetSquibVoltage.response(reply),
+            MeasureVoltageType::Battery    => GetBatteryVoltage.response(reply),
+        }
+    }
+}
+
+// Sensor.
+
+/// Enables the sensors in `mask`, or the default set for `None`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EnableSensorReading(pub Option<u8>);
+
+impl Query for EnableSensorReading {
+    type Response = ();
+
+    fn request(&self) -> UsbCmd {
+        UsbCmd::Sensor(SensorCmd::EnableSensorReading(self.0))
+    }
+    fn response(&self, reply: UsbCmd) -> Result<(), UsbCmd> {
+        match reply {
+            UsbCmd::Sensor(SensorCmd::EnableSensorReading(_)) => Ok(()),
+            r => Err(r),
+        }
+    }
+}
+
+query!(GetSensorReadingEnables -> u8,
+       UsbCmd::Sensor(SensorCmd::GetSensorReadingEnables(0x00)),
+       UsbCmd::Sensor(SensorCmd::GetSensorReadingEnables(mask)) => mask);
+query!(/// x, y, z in g, as are the other accelerometers.
+       GetRightHandAccel -> (f32, f32, f32),
+       UsbCmd::Sensor(SensorCmd::GetRightHandAccel(None)),
+       UsbCmd::Sensor(SensorCmd::GetRightHandAccel(Some(xyz))) => xyz);
+query!(GetLeftHandAccel -> (f32, f32, f32),
+       UsbCmd::Sensor(SensorCmd::GetLeftHandAccel(None)),
+       UsbCmd::Sensor(SensorCmd::GetLeftHandAccel(Some(xyz))) => xyz);
+query!(GetRightFootAccel -> (f32, f32, f32),
+       UsbCmd::Sensor(SensorCmd::GetRightFootAccel(None)),
+       UsbCmd::Sensor(SensorCmd::GetRightFootAccel(Some(xyz))) => xyz);
+query!(GetLeftFootAccel -> (f32, f32, f32),
+       UsbCmd::Sensor(SensorCmd::GetLeftFootAccel(None)),
+       UsbCmd::Sensor(SensorCmd::GetLeftFootAccel(Some(xyz))) => xyz);
+query!(GetBodyAccel -> (f32, f32, f32),
+       UsbCmd::Sensor(SensorCmd::GetBodyAccel(None)),
+       UsbCmd::Sensor(SensorCmd::GetBodyAccel(Some(xyz))) => xyz);
+query!(/// x, y, z in degrees per second.
+       GetGyroscope -> (f32, f32, f32),
+       UsbCmd::Sensor(SensorCmd::GetGyroscope(None)),
+       UsbCmd::Sensor(SensorCmd::GetGyroscope(Some(xyz))) => xyz);
+query!(/// (software, hardware) revision, as are the other sensors.
+       GetSWVRH -> (f32, f32),
+       UsbCmd::Sensor(SensorCmd::GetSWVRH((0.00, 0.00))),
+       UsbCmd::Sensor(SensorCmd::GetSWVRH(rev)) => rev);
+query!(GetSWVLH -> (f32, f32),
+       UsbCmd::Sensor(SensorCmd::GetSWVLH((0.00, 0.00))),
+       UsbCmd::Sensor(SensorCmd::GetSWVLH(rev)) => rev);
+query!(GetSWVRF -> (f32, f32),
+       UsbCmd::Sensor(SensorCmd::GetSWVRF((0.00, 0.00))),
+       UsbCmd::Sensor(SensorCmd::GetSWVRF(rev)) => rev);
+query!(GetSWVLF -> (f32, f32),
+       UsbCmd::Sensor(SensorCmd::GetSWVLF((0.00, 0.00))),
+       UsbCmd::Sensor(SensorCmd::GetSWVLF(rev)) => rev);
+
+impl Query for SensorRevisionType {
+    type Response = (f32, f32);
+
+    fn request(&self) -> UsbCmd {
+        match self {
+            SensorRevisionType::RightHand => GetSWVRH.request(),
+            SensorRevisionType::LeftHand  => GetSWVLH.request(),
+            SensorRevisionType::RightFoot => GetSWVRF.request(),
+            SensorRevisionType::LeftFoot  => GetSWVLF.request(),
+        }
+    }
+    fn response(&self, reply: UsbCmd) -> Result<(f32, f32), UsbCmd> {
+        match self {
+            SensorRevisionType::RightHand => GetSWVRH.response(reply),
+            SensorRevisionType::LeftHand  => GetSWVLH.response(reply),
+            SensorRevisionType::RightFoot => GetSWVRF.response(reply),
+            SensorRevisionType::LeftFoot  => GetSWVLF.response(reply),
+        }
+    }
+}
+
+// Algorithm.
+
+query!(GetAlogrithmThresholds -> Vec<u8>,
+       UsbCmd::Algorithm(AlgorithmCmd::GetAlogrithmThresholds(None)),
+       UsbCmd::Algorithm(AlgorithmCmd::GetAlogrithmThresholds(Some(th))) => th);
+query!(/// The status byte, 0 when the defaults were restored.
+       SetAlogrithmDefaultThresholds -> u8,
+       UsbCmd::Algorithm(AlgorithmCmd::SetAlogrithmDefaultThresholds(None)),
+       UsbCmd::Algorithm(AlgorithmCmd::SetAlogrithmDefaultThresholds(Some(status))) => status);
+
+// Airbag.
+
+query!(/// Ohms for both squibs, `None` when out of range.
+       GetCalibSquibRes -> (Option<f32>, Option<f32>),
+       UsbCmd::Airbag(AirbagCmd::GetCalibSquibRes((None, None))),
+       UsbCmd::Airbag(AirbagCmd::GetCalibSquibRes(res)) => res);
+query!(GetInflationType -> u8,
+       UsbCmd::Airbag(AirbagCmd::GetInflationType(0x00)),
+       UsbCmd::Airbag(AirbagCmd::GetInflationType(it)) => it);
+
+// SWUpdate.
+
+query!(StartBootLoader -> (),
+       UsbCmd::SWUpdate(SWUpdateCmd::StartBootLoader),
+       UsbCmd::SWUpdate(SWUpdateCmd::StartBootLoader) => ());
+query!(GetBootLoaderVersion -> u8,
+       UsbCmd::SWUpdate(SWUpdateCmd::GetBootLoaderVersion(None)),
+       UsbCmd::SWUpdate(SWUpdateCmd::GetBootLoaderVersion(Some(ver))) => ver);
+query!(GetBootLoaderState -> SWUpdateBootLoaderStates,
+       UsbCmd::SWUpdate(SWUpdateCmd::GetBootLoaderState(None)),
+       UsbCmd::SWUpdate(SWUpdateCmd::GetBootLoaderState(Some(state))) => state);
+
+query!(QuitBootLoader -> (),
+       UsbCmd::SWUpdate(SWUpdateCmd::QuitBootLoader),
+       UsbCmd::SWUpdate(SWUpdateCmd::QuitBootLoader) => ());
+
+/// Sends one chunk of the image, the reply is the page count left.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WriteFWData(pub FWData);
+
+impl Query for WriteFWData {
+    type Response = u16;
+
+    fn request(&self) -> UsbCmd {
+        UsbCmd::SWUpdate(SWUpdateCmd::WriteFWData(self.0.clone()))
+    }
+    fn response(&self, reply: UsbCmd) -> Result<u16, UsbCmd> {
+        match reply {
+            UsbCmd::SWUpdate(SWUpdateCmd::WriteFWData(res)) => Ok(res.page_count),
+            r => Err(r),
+        }
+    }
+}
+
+/// Checks the flashed image against its CRC16, non-zero replies mean
+/// the transfer failed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CRCCheck(pub u16);
+
+impl Query for CRCCheck {
+    type Response = u16;
+
+    fn request(&self) -> UsbCmd {
+        UsbCmd::SWUpdate(SWUpdateCmd::CRCCheck(self.0))
+    }
+    fn response(&self, reply: UsbCmd) -> Result<u16, UsbCmd> {
+        match reply {
+            UsbCmd::SWUpdate(SWUpdateCmd::CRCCheck(ret)) => Ok(ret),
+            r => Err(r),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+    use crate::encoder::TechAirResponseEncoder;
+
+    // what the ACU would send back for `cmd`, run through the decoder.
+    fn reply(cmd: UsbCmd) -> UsbCmd {
+        let mut buf = Vec::new();
+        cmd.write_response_bytes(&mut buf);
+        UsbCmd::try_from(buf).unwrap()
+    }
+
+    #[test]
+    fn typed_responses() {
+        let v = reply(UsbCmd::Measure(MeasureCmd::GetBatteryVoltage(3.75)));
+        assert_eq!(GetBatteryVoltage.response(v.clone()), Ok(3.75));
+        assert_eq!(MeasureVoltageType::Battery.response(v.clone()), Ok(3.75));
+        // some other rail's reading is handed back.
+        assert_eq!(GetLogicVoltage.response(v.clone()), Err(v));
+
+        let nr = reply(UsbCmd::General(GeneralCmd::GetSerialNr(Some("TA0000001".to_string()))));
+        assert_eq!(GetSerialNr.response(nr).unwrap(), "TA0000001");
+        assert_eq!(GetSerialNr.request().ids(), (0x00, 0x04));
+        assert_eq!(SensorRevisionType::LeftFoot.request().ids(), (0x04, 0x0b));
+    }
+}
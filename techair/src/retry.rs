@@ -0,0 +1,113 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+
+use std::time::Duration;
+
+use crate::cmd::prelude::*;
+use crate::error::TechAirError;
+use crate::usbcmd::UsbCmd;
+
+
+/// When and how often a failed request is sent again.
+///
+/// Only commands that are safe to repeat (see `CommandMeta::is_idempotent()`)
+/// are ever retried, a lost reply to `ClearPostCrashLog` or
+/// `SetInflationType` always fails straight away.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// Tries in total, including the first one.
+    pub max_attempts: u32,
+    /// Wait before the first retry, doubled for every one after that.
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+    /// Retry when the ACU did not answer in time.
+    pub on_timeout: bool,
+    /// Retry when the reply was damaged, a bad CRC16 or a cut off frame.
+    pub on_corrupt: bool,
+    /// Retry when the reply was for some other request.
+    pub on_unexpected: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy{
+            max_attempts: 3,
+            backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_millis(1000),
+            on_timeout: true,
+            on_corrupt: true,
+            on_unexpected: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Every request gets exactly one try.
+    pub fn never() -> RetryPolicy {
+        RetryPolicy{ max_attempts: 1, ..Default::default() }
+    }
+
+    pub fn is_retryable(&self, err: &TechAirError) -> bool {
+        match err {
+            TechAirError::Timeout => self.on_timeout,
+            TechAirError::CrcMismatch{ .. } |
+            TechAirError::Truncated{ .. } |
+            TechAirError::ShortPayload{ .. } => self.on_corrupt,
+            TechAirError::UnexpectedResponse{ .. } => self.on_unexpected,
+            _ => false,
+        }
+    }
+
+    /// Whether `attempt` (counting from 1) failing with `err` is worth another go.
+    pub fn should_retry(&self, cmd: &UsbCmd, attempt: u32, err: &TechAirError) -> bool {
+        attempt < self.max_attempts && cmd.is_idempotent() && self.is_retryable(err)
+    }
+
+    /// How long to wait after the given failed attempt.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let shift = attempt.saturating_sub(1).min(16);
+        self.backoff.checked_mul(1 << shift)
+            .map_or(self.max_backoff, |d| d.min(self.max_backoff))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_idempotent_commands() {
+        let p = RetryPolicy::default();
+        let get = UsbCmd::General(GeneralCmd::GetSerialNr(None));
+        let clear = UsbCmd::Logging(LoggingCmd::ClearPostCrashLog);
+        let set = UsbCmd::Airbag(AirbagCmd::SetInflationType(0x44));
+        assert!(p.should_retry(&get, 1, &TechAirError::Timeout));
+        assert!(!p.should_retry(&get, 3, &TechAirError::Timeout));
+        assert!(!p.should_retry(&get, 1, &TechAirError::NotFound("".to_string())));
+        assert!(!p.should_retry(&clear, 1, &TechAirError::Timeout));
+        assert!(!p.should_retry(&set, 1, &TechAirError::CrcMismatch{ expected: 0, got: 1 }));
+        assert!(!RetryPolicy::never().should_retry(&get, 1, &TechAirError::Timeout));
+    }
+    #[test]
+    fn backoff_doubles() {
+        let p = RetryPolicy::default();
+        assert_eq!(p.delay(1), Duration::from_millis(50));
+        assert_eq!(p.delay(2), Duration::from_millis(100));
+        assert_eq!(p.delay(3), Duration::from_millis(200));
+        assert_eq!(p.delay(30), p.max_backoff);
+    }
+}
@@ -18,141 +18,423 @@ extern crate serialport;
 extern crate byteorder;
 
 //use serialport::prelude::*;
-use serialport::{SerialPortType,SerialPortInfo};
+use serialport::{SerialPortSettings, SerialPortType, UsbPortInfo};
 
+use std::collections::HashMap;
 use std::convert::TryFrom;
-use std::boxed::Box;
-use std::error::Error;
 use std::fmt;
 use std::time::Duration;
-use std::io::prelude::*;
 
-use crate::usbcmd::{UsbCmd};
+use crate::capture::Capture;
+use crate::query::Query;
+use crate::cmd::prelude::*;
+use crate::cmd::meta::lookup;
+use crate::usbcmd::{UsbCmd, MAX_REQUEST_LEN};
+use crate::error::TechAirError;
 use crate::encoder::TechAirEncoder;
+use crate::framing::{Direction, Framer};
+use crate::transport::{SerialTransport, Transport};
 
 
-pub struct TechAir {
+pub struct TechAir<T: Transport = SerialTransport> {
     port: String,
-    // keep a boxed heap allocation of the trait SerialPort
-    uart: Box<dyn serialport::SerialPort>,
+    link: T,
+    framer: Framer,
+    capture: Option<Capture>,
+    timeout: Duration,
+    // keyed by (group, subcommand), for the few that take their time.
+    cmd_timeouts: HashMap<(u8, u8), Duration>,
+    // what the link is set to right now, None if unknown.
+    link_timeout: Option<Duration>,
+    // anything above this is refused before it goes out.
+    safety_limit: Safety,
 }
 
-impl fmt::Debug for TechAir {
+impl<T: Transport> fmt::Debug for TechAir<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{:?}", self.port)
     }
 }
 
-#[derive(Debug)]
-pub struct TechAirError {
-    details: String
-}
+impl TechAir {
+    pub fn new() -> Result<TechAir, TechAirError> {
+        TechAirBuilder::new().open()
+    }
 
-impl TechAirError {
-    fn new(msg: &str) -> TechAirError {
-        TechAirError{details: msg.to_string()}
+    /// Opens the ACU on a specific port, e.g. one picked from `list_techairs()`.
+    pub fn open(port: &str) -> Result<TechAir, TechAirError> {
+        TechAirBuilder::new().port(port).open()
     }
-}
 
-impl fmt::Display for TechAirError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f,"{}",self.details)
+    pub fn bytes_to_write_left(&mut self) {
+        let uart = self.link.port();
+        trace!("bytes_to_write_left() = {:?}", uart.bytes_to_write());
     }
 }
 
-impl Error for TechAirError {
-    fn description(&self) -> &str {
-        &self.details
+impl<T: Transport> TechAir<T> {
+    pub fn with_transport(link: T) -> TechAir<T> {
+        TechAirBuilder::new().with_transport(link)
     }
-}
 
-impl From<serialport::Error> for TechAirError {
-    fn from(err: serialport::Error) -> TechAirError {
-        TechAirError::new(err.description())
+    /// Records every frame written and read from here on, `None` stops.
+    pub fn set_capture(&mut self, capture: Option<Capture>) {
+        self.capture = capture;
     }
-}
 
-impl TechAir {
-    pub fn new() -> Result<TechAir, TechAirError> {
-        let p = find_techair()?;
-    	let s = serialport::SerialPortSettings{
-    		baud_rate: 115200,
-    		data_bits: serialport::DataBits::Eight,
-    		flow_control: serialport::FlowControl::None,
-    		parity: serialport::Parity::None,
-    		stop_bits: serialport::StopBits::One,
-    		timeout: Duration::from_millis(100) // 2000 R, 500 W ?
-    	};
-        if let Ok(uart) = serialport::open_with_settings(&p, &s) { 
-            uart.clear(serialport::ClearBuffer::All)?;
-            Ok(TechAir{
-                port: p,
-                uart: uart,
-            })
-        } else {
-            Err(TechAirError::new("could not open serial uart"))
+    fn record(&mut self, dir: Direction, frame: &[u8]) {
+        if let Some(c) = self.capture.as_mut() {
+            if let Err(e) = c.record(dir, frame) {
+                warn!("capture failed, no longer recording: {}", e);
+                self.capture = None;
+            }
         }
     }
 
+    /// Sets the timeout for every command without one of its own.
     pub fn set_timeout(&mut self, time: Duration)
-        -> Result<(), serialport::Error> {
-//        -> Result<(), serialport::Error> {
-        let uart = self.uart.as_mut();
-        uart.set_timeout(time)
+        -> Result<(), std::io::Error> {
+        self.timeout = time;
+        self.link_timeout = None;
+        self.link.set_timeout(time)?;
+        self.link_timeout = Some(time);
+        Ok(())
     }
 
-    pub fn bytes_to_write_left(&mut self) {
-        let uart = self.uart.as_mut();
-        println!("DEBUG: bytes_to_write_left() = {:?}", uart.bytes_to_write());
+    /// Commands above `limit` fail with `TechAirError::Refused`.
+    pub fn set_safety_limit(&mut self, limit: Safety) {
+        self.safety_limit = limit;
+    }
+
+    pub fn safety_limit(&self) -> Safety {
+        self.safety_limit
+    }
+
+    /// Overrides the timeout for one (group, subcommand).
+    pub fn set_command_timeout(&mut self, ids: (u8, u8), time: Duration) {
+        self.cmd_timeouts.insert(ids, time);
+    }
+
+    /// The override for `ids` if there is one, else what the command
+    /// table asks for, else the default.
+    pub fn timeout_for(&self, ids: (u8, u8)) -> Duration {
+        self.cmd_timeouts.get(&ids).copied()
+            .or_else(|| lookup(ids.0, ids.1).and_then(|c| c.timeout))
+            .unwrap_or(self.timeout)
     }
 
     pub fn read(&mut self)
-        -> Result<UsbCmd, std::io::Error> {
-            let uart = self.uart.as_mut();
-            let mut buf: Vec<u8> = Vec::new();
-            for byte in uart.bytes() {
-                if let Ok(b) = byte {
-                    buf.push(b);
-                } else {
-                    break;
-                }
-            }
-            if buf.len() > 0 {
-                println!("processing vec = {:#?}", buf);
-                let cmd = UsbCmd::try_from(buf)?;
-                return Ok(cmd);
-            }
-            Err(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "no bytes returned"))
+        -> Result<UsbCmd, TechAirError> {
+            let buf = self.framer.read_frame(&mut self.link)?;
+            trace!("processing vec = {:02x?}", buf);
+            self.record(Direction::Response, &buf);
+            let cmd = UsbCmd::try_from(buf.as_slice())?;
+            Ok(cmd)
     }
 
     pub fn write(&mut self, cmd: UsbCmd)
-        -> std::io::Result<()> {
-            let uart = self.uart.as_mut();
-            let mut buf: Vec<u8> = Vec::new();
-            cmd.write_bytes(&mut buf);
-            //println!("writing buf = {:#04x?}", buf.as_slice());
+        -> Result<(), TechAirError> {
+            if cmd.safety() > self.safety_limit {
+                let (group, sub) = cmd.ids();
+                return Err(TechAirError::Refused{ group, sub, safety: cmd.safety() });
+            }
+            let timeout = self.timeout_for(cmd.ids());
+            if self.link_timeout != Some(timeout) {
+                self.link.set_timeout(timeout)?;
+                self.link_timeout = Some(timeout);
+            }
+            let mut buf = [0u8; MAX_REQUEST_LEN];
+            let len = cmd.encode_to_slice(&mut buf)?;
+            let frame = &buf[..len];
+            trace!("writing {:02x?}", frame);
 // XXX comment out for swupdate work to be NOPed
-            uart.clear(serialport::ClearBuffer::Input)?;
-            uart.write_all(&buf.as_slice())//?;
-            //uart.flush()
-//            cmd.write_bytes(&mut uart)
+            self.link.clear()?;
+            self.framer.clear();
+            self.record(Direction::Request, frame);
+            self.link.write_frame(frame)?;
+            Ok(())
+    }
+}
+
+impl<T: Transport> TechAir<T> {
+    /// Writes `cmd` and reads the reply to it.
+    ///
+    /// The reply has to carry the same group and subcommand as `cmd`,
+    /// anything else (e.g. a late answer to an earlier request) fails with
+    /// `UnexpectedResponse` rather than being taken as the answer.
+    pub fn transact(&mut self, cmd: UsbCmd) -> Result<UsbCmd, TechAirError> {
+        let sent = cmd.ids();
+        self.write(cmd)?;
+        let resp = self.read()?;
+        let received = resp.ids();
+        if received != sent {
+            debug!("sent {:02x?}, got {:02x?}", sent, resp);
+            return Err(TechAirError::UnexpectedResponse{ sent, received });
+        }
+        Ok(resp)
+    }
+
+    /// Sends `q` and decodes the reply to it, see `Query`.
+    pub fn query<Q: Query>(&mut self, q: Q) -> Result<Q::Response, TechAirError> {
+        let resp = self.transact(q.request())?;
+        q.response(resp).map_err(crate::query::invalid)
+    }
+}
+
+/// A connected ACU as seen on the USB bus.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TechAirPort {
+    pub port: String,
+    pub serial_number: Option<String>,
+    pub vid: u16,
+    pub pid: u16,
+}
+
+/// Which USB serial ports are taken to be an ACU.
+///
+/// Every field that is set has to match, so `Default` takes any USB serial
+/// port while `alpinestars()` goes by the strings in the USB descriptors.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DeviceFilter {
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub vid: Option<u16>,
+    pub pid: Option<u16>,
+}
+
+impl DeviceFilter {
+    /// How the ACUs we know of describe themselves.
+    pub fn alpinestars() -> DeviceFilter {
+        DeviceFilter{
+            manufacturer: Some("ALPINESTARS".to_string()),
+            product: Some("Airbag_Control_Unit".to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn usb_id(vid: u16, pid: u16) -> DeviceFilter {
+        DeviceFilter{ vid: Some(vid), pid: Some(pid), ..Default::default() }
     }
+
+    pub fn matches(&self, u: &UsbPortInfo) -> bool {
+        fn same<V: PartialEq>(want: &Option<V>, got: Option<&V>) -> bool {
+            want.is_none() || want.as_ref() == got
+        }
+        same(&self.manufacturer, u.manufacturer.as_ref()) &&
+        same(&self.product, u.product.as_ref()) &&
+        same(&self.vid, Some(&u.vid)) &&
+        same(&self.pid, Some(&u.pid))
+    }
+}
+
+/// Every connected ACU, in the order the OS lists them.
+pub fn list_techairs() -> Result<Vec<TechAirPort>, TechAirError> {
+    list_techairs_matching(&DeviceFilter::alpinestars())
 }
 
-fn find_techair() -> Result<String, TechAirError> {
+pub fn list_techairs_matching(filter: &DeviceFilter) -> Result<Vec<TechAirPort>, TechAirError> {
 	let vap = serialport::available_ports()?;
-        vap.into_iter().filter(filter_sp)
-            .map(|p| p.port_name).next()
-            .ok_or(TechAirError::new("no techair found!"))
+        Ok(vap.into_iter()
+            .filter_map(|p| match p.port_type {
+                SerialPortType::UsbPort(u) if filter.matches(&u) => {
+                    debug!("found an ACU on {}", p.port_name);
+                    Some(TechAirPort{
+                        port: p.port_name,
+                        serial_number: u.serial_number,
+                        vid: u.vid,
+                        pid: u.pid,
+                    })
+                },
+                _ => None,
+            }).collect())
+}
+
+fn find_techair(filter: &DeviceFilter) -> Result<String, TechAirError> {
+        // e.g. the pty of a techair-sim instance.
+        if let Ok(p) = std::env::var("TECHAIR_PORT") {
+            return Ok(p);
+        }
+        list_techairs_matching(filter)?.into_iter()
+            .map(|p| p.port).next()
+            .ok_or_else(|| TechAirError::NotFound("no techair found!".to_string()))
+}
+
+/// Sets up a `TechAir` connection.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use techair::serial::{DeviceFilter, TechAirBuilder};
+///
+/// let ta = TechAirBuilder::new()
+///     .filter(DeviceFilter::usb_id(0x04d8, 0x000a))
+///     .baud_rate(57600)
+///     .timeout(Duration::from_millis(250))
+///     .open();
+/// ```
+#[derive(Clone, Debug)]
+pub struct TechAirBuilder {
+    port: Option<String>,
+    filter: DeviceFilter,
+    settings: SerialPortSettings,
+    cmd_timeouts: HashMap<(u8, u8), Duration>,
+    safety_limit: Safety,
 }
 
-fn filter_sp(sp: &SerialPortInfo) -> bool {
-    if let SerialPortType::UsbPort(ref u) = sp.port_type {
-        if u.manufacturer == Some("ALPINESTARS".to_string()) &&
-           u.product == Some("Airbag_Control_Unit".to_string()) {
-            println!("Found! 'Alpinestars ACU'.");
-            return true;
+impl Default for TechAirBuilder {
+    fn default() -> TechAirBuilder {
+        TechAirBuilder::new()
+    }
+}
+
+impl TechAirBuilder {
+    pub fn new() -> TechAirBuilder {
+        TechAirBuilder{
+            port: None,
+            filter: DeviceFilter::alpinestars(),
+            settings: SerialTransport::default_settings(),
+            cmd_timeouts: HashMap::new(),
+            // never arm or fire the airbag unless asked to.
+            safety_limit: Safety::Destructive,
         }
     }
-    false
+
+    /// Uses this port rather than looking for one.
+    pub fn port(mut self, port: &str) -> TechAirBuilder {
+        self.port = Some(port.to_string());
+        self
+    }
+
+    /// What to look for when no port is given.
+    pub fn filter(mut self, filter: DeviceFilter) -> TechAirBuilder {
+        self.filter = filter;
+        self
+    }
+
+    pub fn settings(mut self, settings: SerialPortSettings) -> TechAirBuilder {
+        self.settings = settings;
+        self
+    }
+
+    pub fn baud_rate(mut self, baud_rate: u32) -> TechAirBuilder {
+        self.settings.baud_rate = baud_rate;
+        self
+    }
+
+    /// The timeout for every command without one of its own.
+    pub fn timeout(mut self, timeout: Duration) -> TechAirBuilder {
+        self.settings.timeout = timeout;
+        self
+    }
+
+    /// Overrides the timeout of one (group, subcommand), see `UsbCmd::ids()`.
+    pub fn command_timeout(mut self, ids: (u8, u8), timeout: Duration) -> TechAirBuilder {
+        self.cmd_timeouts.insert(ids, timeout);
+        self
+    }
+
+    /// The most harmful kind of command that may be sent, see `Safety`.
+    pub fn safety_limit(mut self, limit: Safety) -> TechAirBuilder {
+        self.safety_limit = limit;
+        self
+    }
+
+    pub fn open(self) -> Result<TechAir, TechAirError> {
+        let port = match &self.port {
+            Some(p) => p.clone(),
+            None => find_techair(&self.filter)?,
+        };
+        let link = SerialTransport::open_with_settings(&port, &self.settings)?;
+        let mut ta = self.with_transport(link);
+        ta.port = port;
+        Ok(ta)
+    }
+
+    /// Speaks over `link` instead of a serial port, the serial settings
+    /// are ignored but the timeouts still apply.
+    pub fn with_transport<T: Transport>(self, link: T) -> TechAir<T> {
+        let port = link.name().unwrap_or_else(|| "<transport>".to_string());
+        TechAir{
+            port,
+            link,
+            framer: Framer::new(Direction::Response),
+            capture: None,
+            timeout: self.settings.timeout,
+            cmd_timeouts: self.cmd_timeouts,
+            link_timeout: None,
+            safety_limit: self.safety_limit,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use crate::transport::pipe;
+
+    #[test]
+    fn exchange_over_pipe() {
+        let (host, mut device) = pipe();
+        let acu = thread::spawn(move || {
+            let req = Framer::new(Direction::Request).read_frame(&mut device).unwrap();
+            assert_eq!(req, [0x00,0x04,0x00,0x73]);
+            let mut resp = vec![0x00, 0x04, b'T', b'A', b'0', b'1'];
+            let crc = CRC16::calculate(&resp).as_u16();
+            resp.push((crc & 0xff) as u8);
+            resp.push((crc >>   8) as u8);
+            device.write_frame(&resp).unwrap();
+        });
+        let mut ta = TechAir::with_transport(host);
+        ta.write(UsbCmd::General(GeneralCmd::GetSerialNr(None))).unwrap();
+        let packet = ta.read().unwrap();
+        acu.join().unwrap();
+        assert_eq!(packet, UsbCmd::General(GeneralCmd::GetSerialNr(Some("TA01".to_string()))));
+    }
+    #[test]
+    fn per_command_timeouts() {
+        let (host, _device) = pipe();
+        let mut ta = TechAirBuilder::new()
+            .timeout(Duration::from_millis(20))
+            .command_timeout((0x00, 0x04), Duration::from_millis(5))
+            .with_transport(host);
+        assert_eq!(ta.timeout_for((0x00, 0x02)), Duration::from_millis(20));
+        assert_eq!(ta.timeout_for((0x00, 0x04)), Duration::from_millis(5));
+        assert_eq!(ta.timeout_for((0x20, 0x05)), Duration::from_millis(3000));
+        // nobody answers, so the read has to give up after the 5ms.
+        let start = std::time::Instant::now();
+        ta.write(UsbCmd::General(GeneralCmd::GetSerialNr(None))).unwrap();
+        assert!(matches!(ta.read(), Err(TechAirError::Timeout)));
+        assert!(start.elapsed() < Duration::from_millis(20));
+    }
+    #[test]
+    fn safety_limit() {
+        let (host, mut device) = pipe();
+        let mut ta = TechAir::with_transport(host);
+        let fire = UsbCmd::Airbag(AirbagCmd::FireAIRBAG);
+        assert!(matches!(ta.write(fire.clone()),
+                         Err(TechAirError::Refused{ group: 0x06, sub: 0x05, safety: Safety::Pyrotechnic })));
+        ta.set_safety_limit(Safety::ReadOnly);
+        assert!(matches!(ta.write(UsbCmd::Logging(LoggingCmd::ClearPostCrashLog)),
+                         Err(TechAirError::Refused{ .. })));
+        ta.write(UsbCmd::General(GeneralCmd::GetSerialNr(None))).unwrap();
+        // nothing refused made it onto the wire.
+        let req = Framer::new(Direction::Request).read_frame(&mut device).unwrap();
+        assert_eq!(req, [0x00,0x04,0x00,0x73]);
+    }
+    #[test]
+    fn device_filter() {
+        let u = UsbPortInfo{
+            vid: 0x04d8,
+            pid: 0x000a,
+            serial_number: None,
+            manufacturer: Some("ALPINESTARS".to_string()),
+            product: Some("Airbag_Control_Unit".to_string()),
+        };
+        assert!(DeviceFilter::alpinestars().matches(&u));
+        assert!(DeviceFilter::usb_id(0x04d8, 0x000a).matches(&u));
+        assert!(DeviceFilter::default().matches(&u));
+        assert!(!DeviceFilter::usb_id(0x04d8, 0x000b).matches(&u));
+        let bench = UsbPortInfo{ product: Some("ACU".to_string()), ..u };
+        assert!(!DeviceFilter::alpinestars().matches(&bench));
+    }
 }
@@ -0,0 +1,230 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+
+// Whether an ACU is due for its periodic inspection.
+//
+// The service shop writes the date with `set_service_date()`. The ACU
+// counts operating hours since they were last cleared, which is only the
+// time since the last service where the shop clears them then. There are
+// no limits built in, the caller's policy says how far either may go.
+
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::cmd::prelude::*;
+use crate::error::TechAirError;
+use crate::session::Acu;
+use crate::transport::Transport;
+
+
+/// How long a unit may go between inspections, either limit makes it due.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ServicePolicy {
+    /// Months since the service date.
+    pub max_months: Option<u32>,
+    /// Operating hours since they were last cleared.
+    pub max_op_hours: Option<u32>,
+}
+
+/// Why a unit is due.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DueReason {
+    /// No service date was ever written.
+    NeverServiced,
+    Months{ since: u32, max: u32 },
+    OpHours{ hours: u32, max: u32 },
+}
+
+impl fmt::Display for DueReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DueReason::NeverServiced => write!(f, "never serviced"),
+            DueReason::Months{ since, max } =>
+                write!(f, "{} months since the last service (max {})", since, max),
+            DueReason::OpHours{ hours, max } =>
+                write!(f, "{} operating hours since they were cleared (max {})", hours, max),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServiceStatus {
+    /// `None` when the unit was never serviced.
+    pub service_date: Option<ServiceDate>,
+    /// Whole months since `service_date`, 0 if it is in the future.
+    pub months_since: Option<u32>,
+    /// When the month limit runs out, if there is one.
+    pub next_due: Option<ServiceDate>,
+    /// "hhh:mm:ss" since they were last cleared.
+    pub op_hours: String,
+    /// Reported as read, no policy limits it.
+    pub bat_count: u16,
+    pub due: Vec<DueReason>,
+}
+
+// the whole hours of "hhh:mm:ss".
+fn whole_hours(op_hours: &str) -> Option<u32> {
+    op_hours.split(':').next()?.parse().ok()
+}
+
+impl ServiceStatus {
+    /// Weighs what the ACU reported against `policy` as of `today`.
+    ///
+    /// A unit that was never serviced is always due.
+    pub fn assess(policy: &ServicePolicy, today: ServiceDate, service_date: Option<ServiceDate>,
+                  op_hours: String, bat_count: u16) -> ServiceStatus {
+        let months_since = service_date.map(|d| today.months_since(&d).max(0) as u32);
+        let mut due = Vec::new();
+        match (months_since, policy.max_months) {
+            (None, _) => due.push(DueReason::NeverServiced),
+            (Some(since), Some(max)) if since >= max => due.push(DueReason::Months{ since, max }),
+            _ => {},
+        }
+        if let (Some(max), Some(hours)) = (policy.max_op_hours, whole_hours(&op_hours)) {
+            if hours >= max {
+                due.push(DueReason::OpHours{ hours, max });
+            }
+        }
+        ServiceStatus{
+            service_date,
+            months_since,
+            next_due: service_date.zip(policy.max_months).and_then(|(d, m)| d.add_months(m).ok()),
+            op_hours,
+            bat_count,
+            due,
+        }
+    }
+
+    pub fn is_due(&self) -> bool {
+        !self.due.is_empty()
+    }
+}
+
+impl fmt::Display for ServiceStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.service_date, self.months_since) {
+            (Some(d), Some(since)) => writeln!(f, "service date: {} ({} months ago)", d, since)?,
+            _                      => writeln!(f, "service date: never")?,
+        }
+        if let Some(d) = self.next_due {
+            writeln!(f, "next due:     {}", d)?;
+        }
+        writeln!(f, "op hours:     {}", self.op_hours)?;
+        writeln!(f, "bat count:    {}", self.bat_count)?;
+        if self.due.is_empty() {
+            write!(f, "status:       ok")
+        } else {
+            let why: Vec<String> = self.due.iter().map(|r| r.to_string()).collect();
+            write!(f, "status:       DUE, {}", why.join(", "))
+        }
+    }
+}
+
+/// Today's date by the system clock.
+pub fn today() -> Result<ServiceDate, TechAirError> {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    ServiceDate::from_unix_days((secs / 86_400) as i64)
+        .map_err(|why| TechAirError::InvalidArgument(format!("system clock: {}", why)))
+}
+
+/// Reads the service date, operating hours and bat count off `acu`.
+pub fn service_status<T: Transport>(acu: &mut Acu<T>, policy: &ServicePolicy)
+    -> Result<ServiceStatus, TechAirError> {
+    let service_date = acu.service_date()?;
+    let op_hours = acu.op_hours()?;
+    let bat_count = acu.bat_count()?;
+    Ok(ServiceStatus::assess(policy, today()?, service_date, op_hours, bat_count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> ServiceDate {
+        s.parse().unwrap()
+    }
+    fn serviced(s: &str) -> Option<ServiceDate> {
+        Some(date(s))
+    }
+    const POLICY: ServicePolicy = ServicePolicy{ max_months: Some(24), max_op_hours: Some(800) };
+
+    #[test]
+    fn within_policy() {
+        let s = ServiceStatus::assess(&POLICY, date("10/10/2024"),
+                                      serviced("15/03/2024"), "123:45:06".into(), 7);
+        assert_eq!(s.months_since, Some(6));
+        assert_eq!(s.next_due, Some(date("15/03/2026")));
+        assert!(!s.is_due());
+        assert_eq!(s.bat_count, 7);
+        assert!(s.to_string().contains("\nbat count:    7\n"));
+        assert!(s.to_string().ends_with("status:       ok"));
+    }
+    #[test]
+    fn due_by_either_limit() {
+        let policy = ServicePolicy{ max_months: Some(12), max_op_hours: Some(100) };
+        let s = ServiceStatus::assess(&policy, date("15/03/2025"), serviced("15/03/2024"), "100:00:00".into(), 0);
+        assert_eq!(s.due, vec![DueReason::Months{ since: 12, max: 12 },
+                               DueReason::OpHours{ hours: 100, max: 100 }]);
+        assert!(s.to_string().contains("DUE, 12 months since the last service (max 12), 100 operating hours since they were cleared"));
+
+        let hours_only = ServicePolicy{ max_months: None, ..policy };
+        let s = ServiceStatus::assess(&hours_only, date("15/03/2030"), serviced("15/03/2024"), "99:59:59".into(), 0);
+        assert!(!s.is_due());
+        assert_eq!(s.next_due, None);
+    }
+    #[test]
+    fn date_in_the_future() {
+        let s = ServiceStatus::assess(&POLICY, date("01/01/2024"),
+                                      serviced("15/03/2024"), "0:00:00".into(), 0);
+        assert_eq!(s.months_since, Some(0));
+        assert!(!s.is_due());
+    }
+    #[test]
+    fn never_serviced_is_due() {
+        let policy = ServicePolicy{ max_months: None, max_op_hours: None };
+        let s = ServiceStatus::assess(&policy, date("01/01/2024"), None, "0:00:00".into(), 0);
+        assert_eq!(s.due, vec![DueReason::NeverServiced]);
+        assert_eq!(s.months_since, None);
+        assert_eq!(s.next_due, None);
+        assert!(s.to_string().starts_with("service date: never\n"));
+        assert!(s.to_string().ends_with("status:       DUE, never serviced"));
+    }
+    #[test]
+    fn from_the_simulator() {
+        use crate::sim::{SimConfig, Simulator};
+        use crate::transport::pipe;
+        use crate::framing::{Direction, Framer};
+
+        let (host, mut device) = pipe();
+        let sim = std::thread::spawn(move || {
+            let mut sim = Simulator::new(SimConfig::default());
+            let mut framer = Framer::new(Direction::Request);
+            for _ in 0..3 {
+                let req = framer.read_frame(&mut device).unwrap();
+                device.write_frame(&sim.handle(&req).unwrap()).unwrap();
+            }
+        });
+        let mut acu = Acu::with_transport(host);
+        let s = service_status(&mut acu, &POLICY).unwrap();
+        sim.join().unwrap();
+        assert_eq!(s.service_date, serviced("01/01/2020"));
+        assert_eq!(s.op_hours, "12:34:56");
+        assert_eq!(s.bat_count, SimConfig::default().logs.bat_count);
+        // the simulator was last serviced in 2020.
+        assert!(s.due.contains(&DueReason::Months{ since: s.months_since.unwrap(), max: 24 }));
+    }
+}
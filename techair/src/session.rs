@@ -0,0 +1,533 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use std::thread;
+
+use crate::error::TechAirError;
+use crate::query::{self, Query};
+use crate::retry::RetryPolicy;
+use crate::serial::{list_techairs, TechAir};
+use crate::transport::{SerialTransport, Transport};
+use crate::usbcmd::{UsbCmd, MAX_REQUEST_LEN};
+use crate::cmd::prelude::*;
+use crate::cmd::meta::firmware_number;
+
+
+/// The serial number has no length byte, it runs to the CRC16, so it is
+/// bounded by the longest request frame less the group, subcommand and CRC.
+pub const MAX_SERIAL_NR_LEN: usize = MAX_REQUEST_LEN - 4;
+/// The customer info goes with a one byte length.
+pub const MAX_CUSTOMER_INFO_LEN: usize = u8::MAX as usize;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MeasureVoltageType {
+    Logic,
+    Peripheral,
+    RightHand,
+    LeftHand,
+    RightFoot,
+    LeftFoot,
+    Squib,
+    Battery,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SensorRevisionType {
+    RightHand,
+    LeftHand,
+    RightFoot,
+    LeftFoot,
+}
+
+/// An open connection to one ACU.
+///
+/// The port is found and opened once, every query after that goes over
+/// the same link and hands back a `Result` rather than panicking when the
+/// device goes away.
+pub struct Acu<T: Transport = SerialTransport> {
+    ta: TechAir<T>,
+    retry: RetryPolicy,
+    // firmware_number() of the ACU, asked for the first time it matters.
+    firmware: Option<u16>,
+}
+
+impl Acu {
+    /// Opens the first ACU found.
+    pub fn open() -> Result<Acu, TechAirError> {
+        Ok(Acu::from_techair(TechAir::new()?))
+    }
+
+    pub fn open_port(port: &str) -> Result<Acu, TechAirError> {
+        Ok(Acu::from_techair(TechAir::open(port)?))
+    }
+
+    /// Opens the ACU with the given serial number.
+    ///
+    /// The USB serial string is tried first, failing that each unit is
+    /// asked for its serial number in turn.
+    pub fn open_serial(serial: &str) -> Result<Acu, TechAirError> {
+        let ports = list_techairs()?;
+        if let Some(p) = ports.iter().find(|p| p.serial_number.as_deref() == Some(serial)) {
+            return Acu::open_port(&p.port);
+        }
+        for p in ports {
+            if let Ok(mut acu) = Acu::open_port(&p.port) {
+                if acu.serial_nr().ok().as_deref() == Some(serial) {
+                    return Ok(acu);
+                }
+            }
+        }
+        Err(TechAirError::NotFound(format!("no techair with serial {} found!", serial)))
+    }
+}
+
+impl<T: Transport> Acu<T> {
+    pub fn with_transport(link: T) -> Acu<T> {
+        Acu::from_techair(TechAir::with_transport(link))
+    }
+
+    pub fn from_techair(ta: TechAir<T>) -> Acu<T> {
+        Acu{
+            ta,
+            retry: RetryPolicy::default(),
+            firmware: None,
+        }
+    }
+
+    /// The underlying link, for anything without a typed method.
+    pub fn techair(&mut self) -> &mut TechAir<T> {
+        &mut self.ta
+    }
+
+    pub fn set_retry_policy(&mut self, retry: RetryPolicy) {
+        self.retry = retry;
+    }
+
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry
+    }
+
+    /// Writes `cmd` and waits for the reply, see `TechAir::transact()`.
+    ///
+    /// Transient failures are retried as the session's `RetryPolicy` allows.
+    pub fn request(&mut self, cmd: UsbCmd) -> Result<UsbCmd, TechAirError> {
+        self.check_firmware(&cmd)?;
+        let mut attempt = 1;
+        loop {
+            match self.ta.transact(cmd.clone()) {
+                Err(e) if self.retry.should_retry(&cmd, attempt, &e) => {
+                    let delay = self.retry.delay(attempt);
+                    warn!("{:02x?} failed ({}), retrying in {:?}", cmd.ids(), e, delay);
+                    thread::sleep(delay);
+                    attempt += 1;
+                },
+                r => return r,
+            }
+        }
+    }
+
+    // setters answer with a status byte, anything but 0 means the ACU did
+    // not take the value.
+    fn set<Q: Query<Response = u8>>(&mut self, q: Q) -> Result<(), TechAirError> {
+        let (group, sub) = q.request().ids();
+        match self.query(q)? {
+            0 => Ok(()),
+            status => Err(TechAirError::Rejected{ group, sub, status }),
+        }
+    }
+
+    // commands the ACU answers with an echo, which only says it got them.
+    // Anything but that echo is an error, see `TechAir::transact()`.
+    fn send(&mut self, cmd: UsbCmd) -> Result<(), TechAirError> {
+        self.request(cmd).map(|_| ())
+    }
+
+    /// The ACU software version as the firmware compares it, e.g. 305.
+    pub fn firmware(&mut self) -> Result<u16, TechAirError> {
+        if let Some(fw) = self.firmware {
+            return Ok(fw);
+        }
+        let fw = firmware_number(self.sw_version()?);
+        self.firmware = Some(fw);
+        Ok(fw)
+    }
+
+    // refuses commands the firmware is too old to know.
+    fn check_firmware(&mut self, cmd: &UsbCmd) -> Result<(), TechAirError> {
+        if let Some(min_firmware) = cmd.min_firmware() {
+            let firmware = self.firmware()?;
+            if firmware < min_firmware {
+                let (group, sub) = cmd.ids();
+                return Err(TechAirError::Unsupported{ group, sub, min_firmware, firmware });
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends `q` and decodes the reply to it, retried like `request()`.
+    ///
+    /// ```no_run
+    /// use techair::query::GetLogicVoltage;
+    /// use techair::session::Acu;
+    ///
+    /// let mut acu = Acu::open()?;
+    /// let volts: f32 = acu.query(GetLogicVoltage)?;
+    /// # Ok::<(), techair::error::TechAirError>(())
+    /// ```
+    pub fn query<Q: Query>(&mut self, q: Q) -> Result<Q::Response, TechAirError> {
+        let resp = self.request(q.request())?;
+        q.response(resp).map_err(query::invalid)
+    }
+
+    // General.
+
+    pub fn ctrl_mode(&mut self) -> Result<CtrlMode, TechAirError> {
+        self.query(query::GetCtrlMode)
+    }
+    pub fn sw_version(&mut self) -> Result<f32, TechAirError> {
+        self.query(query::GetSoftwareVersion)
+    }
+    pub fn hw_version(&mut self) -> Result<HardwareVersion, TechAirError> {
+        self.query(query::GetHardwareVersion)
+    }
+    pub fn operating_modus(&mut self) -> Result<OpModus, TechAirError> {
+        self.query(query::GetOperatingModus)
+    }
+    pub fn serial_nr(&mut self) -> Result<String, TechAirError> {
+        self.query(query::GetSerialNr)
+    }
+    pub fn customer_info(&mut self) -> Result<String, TechAirError> {
+        self.query(query::GetCustomerInfo)
+    }
+    /// `None` when the unit was never serviced.
+    pub fn service_date(&mut self) -> Result<Option<ServiceDate>, TechAirError> {
+        self.query(query::GetServiceDate)
+    }
+    pub fn set_ctrl_mode(&mut self, mode: CtrlMode) -> Result<(), TechAirError> {
+        self.set(query::SetCtrlMode(mode))
+    }
+    pub fn set_serial_nr(&mut self, serial: &str) -> Result<(), TechAirError> {
+        if serial.is_empty() || serial.len() > MAX_SERIAL_NR_LEN {
+            return Err(TechAirError::InvalidArgument(
+                format!("serial number must be 1 to {} bytes", MAX_SERIAL_NR_LEN)));
+        }
+        self.set(query::SetSerialNr(serial.to_string()))
+    }
+    pub fn set_hw_version(&mut self, version: HardwareVersion) -> Result<(), TechAirError> {
+        self.set(query::SetHardwareVersion(version))
+    }
+    pub fn set_customer_info(&mut self, info: &str) -> Result<(), TechAirError> {
+        if info.len() > MAX_CUSTOMER_INFO_LEN {
+            return Err(TechAirError::InvalidArgument(
+                format!("customer info is {} bytes, at most {} fit", info.len(), MAX_CUSTOMER_INFO_LEN)));
+        }
+        self.set(query::SetCustomerInfo(info.to_string()))
+    }
+    pub fn set_service_date(&mut self, date: ServiceDate) -> Result<(), TechAirError> {
+        self.set(query::SetServiceDate(date))
+    }
+
+    // Logging.
+
+    pub fn op_hours(&mut self) -> Result<String, TechAirError> {
+        self.query(query::GetOPHours)
+    }
+    pub fn clear_op_hours(&mut self) -> Result<(), TechAirError> {
+        self.send(UsbCmd::Logging(LoggingCmd::ClearOPHours))
+    }
+    pub fn no_of_errors(&mut self) -> Result<u8, TechAirError> {
+        self.query(query::GetNumOfErrors)
+    }
+    pub fn clear_error_history(&mut self) -> Result<(), TechAirError> {
+        self.send(UsbCmd::Logging(LoggingCmd::ClearErrorHistory))
+    }
+    pub fn error_history(&mut self) -> Result<Vec<u8>, TechAirError> {
+        self.query(query::GetErrorHistory)
+    }
+    pub fn no_of_precrash_logs(&mut self) -> Result<u16, TechAirError> {
+        self.query(query::GetNumOfPreCrashLogs)
+    }
+    pub fn precrash_logs(&mut self) -> Result<Vec<u8>, TechAirError> {
+        self.query(query::GetPreCrashLogs)
+    }
+    pub fn clear_precrash_log(&mut self) -> Result<(), TechAirError> {
+        self.send(UsbCmd::Logging(LoggingCmd::ClearPreCrashLog))
+    }
+    pub fn no_of_postcrash_logs(&mut self) -> Result<u16, TechAirError> {
+        self.query(query::GetNumOfPostCrashLogs)
+    }
+    pub fn postcrash_logs(&mut self) -> Result<Vec<u8>, TechAirError> {
+        self.query(query::GetPostCrashLogs)
+    }
+    pub fn clear_postcrash_log(&mut self) -> Result<(), TechAirError> {
+        self.send(UsbCmd::Logging(LoggingCmd::ClearPostCrashLog))
+    }
+    pub fn bat_count(&mut self) -> Result<u16, TechAirError> {
+        self.query(query::GetBatCount)
+    }
+
+    // Measure.
+
+    pub fn voltage(&mut self, rail: MeasureVoltageType) -> Result<f32, TechAirError> {
+        self.query(rail)
+    }
+    pub fn charging_state(&mut self) -> Result<String, TechAirError> {
+        self.query(query::GetChargingState)
+    }
+    pub fn zip_closed(&mut self) -> Result<bool, TechAirError> {
+        self.query(query::GetZIPSwitchState)
+    }
+    pub fn set_led_state(&mut self, state: u8) -> Result<u8, TechAirError> {
+        self.query(query::SetEXTDisplay(state))
+    }
+
+    // Sensor.
+
+    pub fn enable_sensor_reading(&mut self, mask: Option<u8>) -> Result<(), TechAirError> {
+        self.query(query::EnableSensorReading(mask))
+    }
+    /// The mask `enable_sensor_reading()` last set, 0 when none are enabled.
+    pub fn sensor_reading_enables(&mut self) -> Result<u8, TechAirError> {
+        self.query(query::GetSensorReadingEnables)
+    }
+    /// Sensor readings must have been enabled first.
+    pub fn sensor_revision(&mut self, srt: SensorRevisionType) -> Result<(f32, f32), TechAirError> {
+        self.query(srt)
+    }
+
+    // Algorithm.
+
+    pub fn init_algorithm(&mut self) -> Result<(), TechAirError> {
+        self.send(UsbCmd::Algorithm(AlgorithmCmd::InitAlgorithm))
+    }
+    pub fn algorithm_thresholds(&mut self) -> Result<Vec<u8>, TechAirError> {
+        self.query(query::GetAlogrithmThresholds)
+    }
+    pub fn set_algorithm_default_thresholds(&mut self) -> Result<(), TechAirError> {
+        self.set(query::SetAlogrithmDefaultThresholds)
+    }
+
+    // Airbag.
+
+    pub fn calib_squib_res(&mut self) -> Result<(Option<f32>, Option<f32>), TechAirError> {
+        self.query(query::GetCalibSquibRes)
+    }
+    pub fn inflation_type(&mut self) -> Result<u8, TechAirError> {
+        self.query(query::GetInflationType)
+    }
+    pub fn set_inflation_type(&mut self, it: u8) -> Result<(), TechAirError> {
+        self.send(UsbCmd::Airbag(AirbagCmd::SetInflationType(it)))
+    }
+
+    // SWUpdate.
+
+    pub fn bootloader_version(&mut self) -> Result<u8, TechAirError> {
+        self.query(query::GetBootLoaderVersion)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use crate::framing::{Direction, Framer};
+    use crate::sim::{SimConfig, Simulator};
+    use crate::transport::pipe;
+
+    // serves exactly `n` requests from a simulator on the other end.
+    fn acu(n: usize) -> (Acu<crate::transport::PipeTransport>, thread::JoinHandle<()>) {
+        acu_with(SimConfig::default(), n)
+    }
+
+    fn acu_with(cfg: SimConfig, n: usize)
+        -> (Acu<crate::transport::PipeTransport>, thread::JoinHandle<()>) {
+        let (host, mut device) = pipe();
+        let sim = thread::spawn(move || {
+            let mut sim = Simulator::new(cfg);
+            let mut framer = Framer::new(Direction::Request);
+            for _ in 0..n {
+                let req = framer.read_frame(&mut device).unwrap();
+                if let Some(resp) = sim.handle(&req) {
+                    device.write_frame(&resp).unwrap();
+                }
+            }
+        });
+        (Acu::with_transport(host), sim)
+    }
+
+    #[test]
+    fn reuses_one_link() {
+        let (mut acu, sim) = acu(4);
+        assert_eq!(acu.serial_nr().unwrap(), "TA0000001");
+        assert_eq!(acu.customer_info().unwrap(), "ALPINESTARS");
+        assert_eq!(acu.bat_count().unwrap(), SimConfig::default().logs.bat_count);
+        assert_eq!(acu.zip_closed().unwrap(), SimConfig::default().zip_closed);
+        sim.join().unwrap();
+    }
+    #[test]
+    fn never_serviced() {
+        let blank = SimConfig{ service_date: String::new(), ..SimConfig::default() };
+        let (mut acu, sim) = acu_with(blank, 1);
+        assert_eq!(acu.service_date().unwrap(), None);
+        sim.join().unwrap();
+    }
+    #[test]
+    fn checks_min_firmware() {
+        let (mut older, sim) = acu_with(SimConfig{ sw_version: 2.5, ..SimConfig::default() }, 1);
+        assert!(matches!(older.set_algorithm_default_thresholds(),
+                         Err(TechAirError::Unsupported{ min_firmware: 279, firmware: 250, .. })));
+        // the version is only asked for once.
+        assert!(older.set_algorithm_default_thresholds().is_err());
+        sim.join().unwrap();
+
+        let (mut acu, sim) = acu(2);
+        acu.set_algorithm_default_thresholds().unwrap();
+        sim.join().unwrap();
+        assert_eq!(acu.firmware().unwrap(), 305);
+    }
+    #[test]
+    fn replays_a_capture() {
+        use std::io::Write;
+        use std::sync::{Arc, Mutex};
+        use crate::capture::{read_capture, Capture};
+        use crate::transport::ReplayTransport;
+
+        #[derive(Clone, Default)]
+        struct Shared(Arc<Mutex<Vec<u8>>>);
+        impl Write for Shared {
+            fn write(&mut self, b: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(b)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let out = Shared::default();
+        let (mut acu, sim) = acu(2);
+        acu.techair().set_capture(Some(Capture::new(out.clone()).unwrap()));
+        let serial = crate::cli::get_serial_nr(&mut acu).unwrap();
+        let bat = crate::cli::get_bat_count(&mut acu).unwrap();
+        sim.join().unwrap();
+
+        let text = out.0.lock().unwrap().clone();
+        let entries = read_capture(text.as_slice()).unwrap();
+        assert_eq!(entries.len(), 4);
+        let mut acu = Acu::with_transport(ReplayTransport::new(entries));
+        assert_eq!(crate::cli::get_serial_nr(&mut acu).unwrap(), serial);
+        assert_eq!(crate::cli::get_bat_count(&mut acu).unwrap(), bat);
+        assert!(acu.serial_nr().is_err());
+    }
+    #[test]
+    fn mismatched_reply_is_an_error() {
+        use crate::encoder::TechAirResponseEncoder;
+
+        let (host, mut device) = pipe();
+        let acu_side = thread::spawn(move || {
+            // a late answer to some earlier GetBatCount.
+            Framer::new(Direction::Request).read_frame(&mut device).unwrap();
+            let mut buf = Vec::new();
+            UsbCmd::Logging(LoggingCmd::GetBatCount(Some(3))).write_response_bytes(&mut buf);
+            device.write_frame(&buf).unwrap();
+        });
+        let mut acu = Acu::with_transport(host);
+        acu.set_retry_policy(RetryPolicy::never());
+        match acu.serial_nr() {
+            Err(TechAirError::UnexpectedResponse{ sent: (0x00, 0x04), received: (0x01, 0x0e) }) => {},
+            r => panic!("{:?}", r),
+        }
+        acu_side.join().unwrap();
+    }
+    #[test]
+    fn clears_wait_for_the_echo() {
+        let (mut acu, sim) = acu(3);
+        acu.clear_op_hours().unwrap();
+        acu.set_inflation_type(0x44).unwrap();
+        assert_eq!(acu.op_hours().unwrap(), "00:00:00");
+        sim.join().unwrap();
+    }
+    #[test]
+    fn missing_echo_is_an_error() {
+        let (mut acu, sim) = acu(0);
+        sim.join().unwrap();
+        acu.techair().set_timeout(std::time::Duration::from_millis(1)).unwrap();
+        assert!(matches!(acu.clear_error_history(), Err(TechAirError::Timeout)));
+    }
+    #[test]
+    fn setters_check_the_status() {
+        use crate::encoder::TechAirResponseEncoder;
+
+        let (host, mut device) = pipe();
+        let acu_side = thread::spawn(move || {
+            let mut sim = Simulator::new(SimConfig::default());
+            let mut framer = Framer::new(Direction::Request);
+            let req = framer.read_frame(&mut device).unwrap();
+            device.write_frame(&sim.handle(&req).unwrap()).unwrap();
+            // the next one is turned down.
+            framer.read_frame(&mut device).unwrap();
+            let mut buf = Vec::new();
+            UsbCmd::General(GeneralCmd::SetServiceDate(Setting::Status(0x01))).write_response_bytes(&mut buf);
+            device.write_frame(&buf).unwrap();
+        });
+        let mut acu = Acu::with_transport(host);
+        acu.set_retry_policy(RetryPolicy::never());
+        let date = ServiceDate::new(15, 3, 2024).unwrap();
+        acu.set_service_date(date).unwrap();
+        match acu.set_service_date(date) {
+            Err(TechAirError::Rejected{ group: 0x00, sub: 0x0b, status: 0x01 }) => {},
+            r => panic!("{:?}", r),
+        }
+        acu_side.join().unwrap();
+    }
+    #[test]
+    fn retries_lost_replies() {
+        let (host, mut device) = pipe();
+        let acu_side = thread::spawn(move || {
+            let mut sim = Simulator::new(SimConfig::default());
+            let mut framer = Framer::new(Direction::Request);
+            // swallow the first try of each request.
+            for n in 0..3 {
+                let req = framer.read_frame(&mut device).unwrap();
+                if n % 2 == 1 {
+                    device.write_frame(&sim.handle(&req).unwrap()).unwrap();
+                }
+            }
+        });
+        let mut acu = Acu::with_transport(host);
+        acu.techair().set_timeout(std::time::Duration::from_millis(20)).unwrap();
+        acu.set_retry_policy(RetryPolicy{ backoff: std::time::Duration::from_millis(0), ..Default::default() });
+        assert_eq!(acu.serial_nr().unwrap(), "TA0000001");
+        // never resent, so the lost reply is an error.
+        assert!(matches!(acu.set_led_state(0x03), Err(TechAirError::Timeout)));
+        acu_side.join().unwrap();
+    }
+    #[test]
+    fn setters_check_their_values() {
+        let (mut acu, sim) = acu(0);
+        sim.join().unwrap();
+        let bad = |r| matches!(r, Err(TechAirError::InvalidArgument(_)));
+        assert!(bad(acu.set_serial_nr("")));
+        assert!(bad(acu.set_serial_nr(&"x".repeat(MAX_SERIAL_NR_LEN + 1))));
+        assert!(bad(acu.set_customer_info(&"x".repeat(MAX_CUSTOMER_INFO_LEN + 1))));
+    }
+    #[test]
+    fn disconnect_is_an_error() {
+        let (mut acu, sim) = acu(0);
+        sim.join().unwrap();
+        acu.techair().set_timeout(std::time::Duration::from_millis(1)).unwrap();
+        assert!(matches!(acu.sw_version(), Err(TechAirError::Timeout)));
+    }
+}
@@ -0,0 +1,534 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+// A software stand-in for the ACU: answers every UsbCmd the way a unit
+// would, using the same cmd codecs in reverse.
+
+use std::io;
+
+use serde::Deserialize;
+
+use crate::cmd::prelude::*;
+use crate::encoder::TechAirEncoder;
+use crate::frame::{Direction, Frame};
+use crate::framing::Framer;
+use crate::transport::Transport;
+use crate::usbcmd::UsbCmd;
+
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct SimConfig {
+    pub serial_nr: String,
+    pub customer_info: String,
+    pub sw_version: f32,
+    pub hw_version: f32,
+    pub ctrl_mode: u8,
+    pub operating_mode: u8,
+    pub operating_addons: u8,
+    // "dd/mm/yyyy", anything else answers as never serviced.
+    pub service_date: String,
+    pub inflation_type: u8,
+    pub squib_res: (f32, f32),
+    pub charging_state: u8,
+    pub zip_closed: bool,
+    pub algorithm_thresholds: Vec<u8>,
+    pub voltages: SimVoltages,
+    pub sensors: SimSensors,
+    pub logs: SimLogs,
+    pub bootloader: SimBootloader,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct SimVoltages {
+    pub logic: f32,
+    pub peripheral: f32,
+    pub right_hand: f32,
+    pub left_hand: f32,
+    pub right_foot: f32,
+    pub left_foot: f32,
+    pub squib: f32,
+    pub battery: f32,
+}
+
+// (software, hardware) revision and (x, y, z) readings per sensor.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct SimSensors {
+    pub enables: u8,
+    pub right_hand_rev: (f32, f32),
+    pub left_hand_rev: (f32, f32),
+    pub right_foot_rev: (f32, f32),
+    pub left_foot_rev: (f32, f32),
+    pub right_hand_accel: (f32, f32, f32),
+    pub left_hand_accel: (f32, f32, f32),
+    pub right_foot_accel: (f32, f32, f32),
+    pub left_foot_accel: (f32, f32, f32),
+    pub body_accel: (f32, f32, f32),
+    pub gyroscope: (f32, f32, f32),
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct SimLogs {
+    pub op_hours: String,
+    pub errors: u8,
+    pub error_history: Vec<u8>,
+    pub precrash: u16,
+    pub postcrash: u16,
+    pub precrash_logs: Vec<u8>,
+    pub postcrash_logs: Vec<u8>,
+    pub bat_count: u16,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct SimBootloader {
+    pub version: u8,
+    // what CRCCheck answers with, non-zero fails the update.
+    pub crc_check: u8,
+}
+
+impl Default for SimConfig {
+    fn default() -> SimConfig {
+        SimConfig{
+            serial_nr: "TA0000001".to_string(),
+            customer_info: "ALPINESTARS".to_string(),
+            sw_version: 3.05,
+            hw_version: 1.0,
+            ctrl_mode: 0x00,
+            operating_mode: 0xaa, // Street
+            operating_addons: 0x00,
+            service_date: "01/01/2020".to_string(),
+            inflation_type: 0xbb, // double
+            squib_res: (2.1, 2.1),
+            charging_state: 0x0a, // USB Power OK, Charge done
+            zip_closed: true,
+            algorithm_thresholds: vec![0x00; 16],
+            voltages: SimVoltages::default(),
+            sensors: SimSensors::default(),
+            logs: SimLogs::default(),
+            bootloader: SimBootloader::default(),
+        }
+    }
+}
+
+impl Default for SimVoltages {
+    fn default() -> SimVoltages {
+        SimVoltages{
+            logic: 3.3,
+            peripheral: 5.0,
+            right_hand: 3.3,
+            left_hand: 3.3,
+            right_foot: 3.3,
+            left_foot: 3.3,
+            squib: 12.0,
+            battery: 3.9,
+        }
+    }
+}
+
+impl Default for SimSensors {
+    fn default() -> SimSensors {
+        SimSensors{
+            enables: 0x35,
+            right_hand_rev: (1.02, 1.0),
+            left_hand_rev: (1.02, 1.0),
+            right_foot_rev: (1.02, 1.0),
+            left_foot_rev: (1.02, 1.0),
+            right_hand_accel: (0.0, 0.0, 9.8),
+            left_hand_accel: (0.0, 0.0, 9.8),
+            right_foot_accel: (0.0, 0.0, 9.8),
+            left_foot_accel: (0.0, 0.0, 9.8),
+            body_accel: (0.0, 0.0, 9.8),
+            gyroscope: (0.0, 0.0, 0.0),
+        }
+    }
+}
+
+impl Default for SimLogs {
+    fn default() -> SimLogs {
+        SimLogs{
+            op_hours: "12:34:56".to_string(),
+            errors: 0,
+            error_history: vec![0x00],
+            precrash: 0,
+            postcrash: 0,
+            precrash_logs: vec![0x00],
+            postcrash_logs: vec![0x00],
+            bat_count: 0,
+        }
+    }
+}
+
+impl Default for SimBootloader {
+    fn default() -> SimBootloader {
+        SimBootloader{
+            version: 4,
+            crc_check: 0,
+        }
+    }
+}
+
+pub struct Simulator {
+    cfg: SimConfig,
+    bl_state: SWUpdateBootLoaderStates,
+    leds: u8,
+}
+
+impl Simulator {
+    pub fn new(cfg: SimConfig) -> Simulator {
+        Simulator{
+            cfg,
+            bl_state: SWUpdateBootLoaderStates::WaitFW,
+            leds: 0x00,
+        }
+    }
+
+    pub fn config(&self) -> &SimConfig {
+        &self.cfg
+    }
+
+    pub fn leds(&self) -> u8 {
+        self.leds
+    }
+
+    /// Decodes one request frame and encodes the reply, `None` for
+    /// anything a unit would not answer.
+    pub fn handle(&mut self, frame: &[u8]) -> Option<Vec<u8>> {
+        match Frame::decode(Direction::Request, frame) {
+            Ok(req) => {
+                let mut buf = Vec::new();
+                match Frame::response(self.respond(req.cmd)).encode(&mut buf) {
+                    Ok(()) => Some(buf),
+                    // e.g. op hours in the config that are not hhh:mm:ss.
+                    Err(e) => {
+                        warn!("sim: cannot answer {:02x?}, check the config: {}", frame, e);
+                        None
+                    },
+                }
+            },
+            Err(e) => {
+                warn!("sim: dropping {:02x?}: {}", frame, e);
+                None
+            },
+        }
+    }
+
+    /// Answers requests on `link` until it fails.
+    pub fn serve<T: Transport>(&mut self, link: &mut T) -> io::Result<()> {
+        let mut framer = Framer::new(Direction::Request);
+        loop {
+            match framer.read_frame(link) {
+                Ok(frame) => {
+                    if let Some(resp) = self.handle(&frame) {
+                        link.write_frame(&resp)?;
+                    }
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    pub fn respond(&mut self, req: UsbCmd) -> UsbCmd {
+        match req {
+            UsbCmd::General(cmd) => UsbCmd::General(self.general(cmd)),
+            UsbCmd::Logging(cmd) => UsbCmd::Logging(self.logging(cmd)),
+            UsbCmd::Power(cmd) => UsbCmd::Power(cmd),
+            UsbCmd::Measure(cmd) => UsbCmd::Measure(self.measure(cmd)),
+            UsbCmd::Sensor(cmd) => UsbCmd::Sensor(self.sensor(cmd)),
+            UsbCmd::Algorithm(cmd) => UsbCmd::Algorithm(self.algorithm(cmd)),
+            UsbCmd::Airbag(cmd) => UsbCmd::Airbag(self.airbag(cmd)),
+            UsbCmd::SWUpdate(cmd) => UsbCmd::SWUpdate(self.swupdate(cmd)),
+        }
+    }
+
+    fn general(&mut self, cmd: GeneralCmd) -> GeneralCmd {
+        let cfg = &self.cfg;
+        match cmd {
+            GeneralCmd::GetCtrlMode(_) => GeneralCmd::GetCtrlMode(Some(CtrlMode(cfg.ctrl_mode))),
+            GeneralCmd::GetSoftwareVersion(_) => GeneralCmd::GetSoftwareVersion(Some(cfg.sw_version)),
+            GeneralCmd::GetOperatingModus(_) => {
+                let modus = OpModus::from_raw(cfg.operating_mode, cfg.operating_addons);
+                GeneralCmd::GetOperatingModus(Some(modus))
+            },
+            GeneralCmd::GetSerialNr(_) => GeneralCmd::GetSerialNr(Some(cfg.serial_nr.clone())),
+            GeneralCmd::GetHardwareVersion(_) => {
+                GeneralCmd::GetHardwareVersion(Some(HardwareVersion::from_f32(cfg.hw_version)))
+            },
+            GeneralCmd::GetCustomerInfo(_) => GeneralCmd::GetCustomerInfo(Some(cfg.customer_info.clone())),
+            GeneralCmd::GetServiceDate(_) => GeneralCmd::GetServiceDate(cfg.service_date.parse().ok()),
+            GeneralCmd::SetCtrlMode(Setting::Value(mode)) => {
+                self.cfg.ctrl_mode = mode.0;
+                GeneralCmd::SetCtrlMode(Setting::Status(0))
+            },
+            GeneralCmd::SetSerialNr(Setting::Value(serial)) => {
+                self.cfg.serial_nr = serial;
+                GeneralCmd::SetSerialNr(Setting::Status(0))
+            },
+            GeneralCmd::SetHardwareVersion(Setting::Value(ver)) => {
+                self.cfg.hw_version = ver.as_f32();
+                GeneralCmd::SetHardwareVersion(Setting::Status(0))
+            },
+            GeneralCmd::SetCustomerInfo(Setting::Value(info)) => {
+                self.cfg.customer_info = info;
+                GeneralCmd::SetCustomerInfo(Setting::Status(0))
+            },
+            GeneralCmd::SetServiceDate(Setting::Value(date)) => {
+                self.cfg.service_date = date.to_string();
+                GeneralCmd::SetServiceDate(Setting::Status(0))
+            },
+            // a status only ever comes from the ACU, not in a request.
+            other => other,
+        }
+    }
+
+    fn logging(&mut self, cmd: LoggingCmd) -> LoggingCmd {
+        let logs = &mut self.cfg.logs;
+        match cmd {
+            LoggingCmd::GetOPHours(_) => LoggingCmd::GetOPHours(Some(logs.op_hours.clone())),
+            LoggingCmd::ClearOPHours => {
+                logs.op_hours = "00:00:00".to_string();
+                LoggingCmd::ClearOPHours
+            },
+            LoggingCmd::GetNumOfErrors(_) => LoggingCmd::GetNumOfErrors(Some(logs.errors)),
+            LoggingCmd::GetErrorEntry(_) => LoggingCmd::GetErrorEntry(Some(logs.error_history.clone())),
+            LoggingCmd::ClearErrorHistory => {
+                logs.errors = 0;
+                logs.error_history = vec![0x00];
+                LoggingCmd::ClearErrorHistory
+            },
+            LoggingCmd::GetNumOfPreCrashLogs(_) => LoggingCmd::GetNumOfPreCrashLogs(Some(logs.precrash)),
+            LoggingCmd::GetPreCrashLogs(_) => LoggingCmd::GetPreCrashLogs(Some(logs.precrash_logs.clone())),
+            LoggingCmd::ClearPreCrashLog => {
+                logs.precrash = 0;
+                LoggingCmd::ClearPreCrashLog
+            },
+            LoggingCmd::GetNumOfPostCrashLogs(_) => LoggingCmd::GetNumOfPostCrashLogs(Some(logs.postcrash)),
+            LoggingCmd::ClearPostCrashLog => {
+                logs.postcrash = 0;
+                LoggingCmd::ClearPostCrashLog
+            },
+            LoggingCmd::GetErrorHistory(_) => LoggingCmd::GetErrorHistory(Some(logs.error_history.clone())),
+            LoggingCmd::GetPostCrashLogs(_) => LoggingCmd::GetPostCrashLogs(Some(logs.postcrash_logs.clone())),
+            LoggingCmd::GetBatCount(_) => LoggingCmd::GetBatCount(Some(logs.bat_count)),
+            other => other,
+        }
+    }
+
+    fn measure(&mut self, cmd: MeasureCmd) -> MeasureCmd {
+        let v = &self.cfg.voltages;
+        match cmd {
+            MeasureCmd::GetLogicVoltage(_) => MeasureCmd::GetLogicVoltage(v.logic),
+            MeasureCmd::GetPeripheralVoltage(_) => MeasureCmd::GetPeripheralVoltage(v.peripheral),
+            MeasureCmd::GetRightHandVoltage(_) => MeasureCmd::GetRightHandVoltage(v.right_hand),
+            MeasureCmd::GetLeftHandVoltage(_) => MeasureCmd::GetLeftHandVoltage(v.left_hand),
+            MeasureCmd::GetRightFootVoltage(_) => MeasureCmd::GetRightFootVoltage(v.right_foot),
+            MeasureCmd::GetLeftFootVoltage(_) => MeasureCmd::GetLeftFootVoltage(v.left_foot),
+            MeasureCmd::GetSquibVoltage(_) => MeasureCmd::GetSquibVoltage(v.squib),
+            MeasureCmd::GetBatteryVoltage(_) => MeasureCmd::GetBatteryVoltage(v.battery),
+            MeasureCmd::GetChargingState(_) => {
+                let state = decode_charging_state(self.cfg.charging_state);
+                MeasureCmd::GetChargingState(Some(state))
+            },
+            MeasureCmd::GetZIPSwitchState(_) => MeasureCmd::GetZIPSwitchState(self.cfg.zip_closed),
+            MeasureCmd::SetEXTDisplay(leds) => {
+                self.leds = leds;
+                MeasureCmd::SetEXTDisplay(leds)
+            },
+        }
+    }
+
+    fn sensor(&mut self, cmd: SensorCmd) -> SensorCmd {
+        let s = &mut self.cfg.sensors;
+        match cmd {
+            SensorCmd::EnableSensorReading(mask) => {
+                if let Some(m) = mask {
+                    s.enables = m;
+                }
+                SensorCmd::EnableSensorReading(mask)
+            },
+            SensorCmd::GetSensorReadingEnables(_) => SensorCmd::GetSensorReadingEnables(s.enables),
+            SensorCmd::GetRightHandAccel(_) => SensorCmd::GetRightHandAccel(Some(s.right_hand_accel)),
+            SensorCmd::GetLeftHandAccel(_) => SensorCmd::GetLeftHandAccel(Some(s.left_hand_accel)),
+            SensorCmd::GetRightFootAccel(_) => SensorCmd::GetRightFootAccel(Some(s.right_foot_accel)),
+            SensorCmd::GetLeftFootAccel(_) => SensorCmd::GetLeftFootAccel(Some(s.left_foot_accel)),
+            SensorCmd::GetBodyAccel(_) => SensorCmd::GetBodyAccel(Some(s.body_accel)),
+            SensorCmd::GetGyroscope(_) => SensorCmd::GetGyroscope(Some(s.gyroscope)),
+            SensorCmd::GetSWVRH(_) => SensorCmd::GetSWVRH(s.right_hand_rev),
+            SensorCmd::GetSWVLH(_) => SensorCmd::GetSWVLH(s.left_hand_rev),
+            SensorCmd::GetSWVRF(_) => SensorCmd::GetSWVRF(s.right_foot_rev),
+            SensorCmd::GetSWVLF(_) => SensorCmd::GetSWVLF(s.left_foot_rev),
+        }
+    }
+
+    fn algorithm(&mut self, cmd: AlgorithmCmd) -> AlgorithmCmd {
+        match cmd {
+            AlgorithmCmd::GetAlogrithmThresholds(_) => {
+                AlgorithmCmd::GetAlogrithmThresholds(Some(self.cfg.algorithm_thresholds.clone()))
+            },
+            AlgorithmCmd::SetAlogrithmDefaultThresholds(_) => {
+                AlgorithmCmd::SetAlogrithmDefaultThresholds(Some(0x00))
+            },
+            other => other,
+        }
+    }
+
+    fn airbag(&mut self, cmd: AirbagCmd) -> AirbagCmd {
+        match cmd {
+            AirbagCmd::GetCalibSquibRes(_) => {
+                let (r0, r1) = self.cfg.squib_res;
+                AirbagCmd::GetCalibSquibRes((Some(r0), Some(r1)))
+            },
+            AirbagCmd::GetInflationType(_) => AirbagCmd::GetInflationType(self.cfg.inflation_type),
+            AirbagCmd::SetInflationType(it) => {
+                self.cfg.inflation_type = it;
+                AirbagCmd::SetInflationType(it)
+            },
+            AirbagCmd::FireAIRBAG => {
+                warn!("sim: FireAIRBAG received, ignoring");
+                AirbagCmd::FireAIRBAG
+            },
+            other => other,
+        }
+    }
+
+    fn swupdate(&mut self, cmd: SWUpdateCmd) -> SWUpdateCmd {
+        match cmd {
+            SWUpdateCmd::StartBootLoader => {
+                self.bl_state = SWUpdateBootLoaderStates::WaitFW;
+                SWUpdateCmd::StartBootLoader
+            },
+            SWUpdateCmd::GetBootLoaderVersion(_) => {
+                SWUpdateCmd::GetBootLoaderVersion(Some(self.cfg.bootloader.version))
+            },
+            SWUpdateCmd::GetBootLoaderState(_) => {
+                let state = self.bl_state.clone();
+                // walk through flashing on each poll once all pages are in.
+                self.bl_state = match &state {
+                    SWUpdateBootLoaderStates::CheckReadFW => SWUpdateBootLoaderStates::EraseFlash,
+                    SWUpdateBootLoaderStates::EraseFlash => SWUpdateBootLoaderStates::FlashFW,
+                    SWUpdateBootLoaderStates::FlashFW => SWUpdateBootLoaderStates::WaitVerifyFlashedCRC,
+                    SWUpdateBootLoaderStates::CalcFlashedCRC => SWUpdateBootLoaderStates::WaitFW,
+                    s => s.clone(),
+                };
+                SWUpdateCmd::GetBootLoaderState(Some(state))
+            },
+            SWUpdateCmd::WriteFWData(fw_data) => {
+                // the ACU counts pages down and answers 0 after the last.
+                let page_count = if fw_data.chunk_len() >= 255 {
+                    fw_data.page_count.saturating_sub(1)
+                } else { 0 };
+                if page_count == 0 {
+                    self.bl_state = SWUpdateBootLoaderStates::CheckReadFW;
+                }
+                let chunk = [0x00];
+                SWUpdateCmd::WriteFWData(FWData::new(&chunk, &page_count).unwrap())
+            },
+            SWUpdateCmd::QuitBootLoader => {
+                self.bl_state = SWUpdateBootLoaderStates::WaitFW;
+                SWUpdateCmd::QuitBootLoader
+            },
+            SWUpdateCmd::CRCCheck(_) => {
+                self.bl_state = SWUpdateBootLoaderStates::CalcFlashedCRC;
+                SWUpdateCmd::CRCCheck(self.cfg.bootloader.crc_check as u16)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use crate::serial::TechAir;
+    use crate::transport::pipe;
+
+    fn exchange(sim: &mut Simulator, req: UsbCmd) -> UsbCmd {
+        let (host, mut device) = pipe();
+        let mut ta = TechAir::with_transport(host);
+        ta.write(req).unwrap();
+        let frame = Framer::new(Direction::Request).read_frame(&mut device).unwrap();
+        device.write_frame(&sim.handle(&frame).unwrap()).unwrap();
+        ta.read().unwrap()
+    }
+
+    #[test]
+    fn answers_from_config() {
+        let mut sim = Simulator::new(SimConfig::default());
+        let resp = exchange(&mut sim, UsbCmd::General(GeneralCmd::GetSerialNr(None)));
+        assert_eq!(resp, UsbCmd::General(GeneralCmd::GetSerialNr(Some("TA0000001".to_string()))));
+        let resp = exchange(&mut sim, UsbCmd::General(GeneralCmd::GetCustomerInfo(None)));
+        assert_eq!(resp, UsbCmd::General(GeneralCmd::GetCustomerInfo(Some("ALPINESTARS".to_string()))));
+        let resp = exchange(&mut sim, UsbCmd::General(GeneralCmd::GetServiceDate(None)));
+        assert_eq!(resp, UsbCmd::General(GeneralCmd::GetServiceDate(ServiceDate::new(1, 1, 2020).ok())));
+        let resp = exchange(&mut sim, UsbCmd::General(GeneralCmd::GetHardwareVersion(None)));
+        assert_eq!(resp, UsbCmd::General(GeneralCmd::GetHardwareVersion(Some(HardwareVersion::from_raw(100)))));
+        let resp = exchange(&mut sim, UsbCmd::Logging(LoggingCmd::GetOPHours(None)));
+        assert_eq!(resp, UsbCmd::Logging(LoggingCmd::GetOPHours(Some("12:34:56".to_string()))));
+        let resp = exchange(&mut sim, UsbCmd::Measure(MeasureCmd::GetChargingState(None)));
+        assert_eq!(resp, UsbCmd::Measure(MeasureCmd::GetChargingState(Some("USB Power OK, Charge done".to_string()))));
+    }
+    #[test]
+    fn bad_config_is_not_sent() {
+        let mut cfg = SimConfig::default();
+        cfg.logs.op_hours = "12:34".to_string();
+        let mut sim = Simulator::new(cfg);
+        let mut frame = Vec::new();
+        UsbCmd::Logging(LoggingCmd::GetOPHours(None)).write_bytes(&mut frame);
+        assert_eq!(sim.handle(&frame), None);
+    }
+    #[test]
+    fn keeps_state() {
+        let mut sim = Simulator::new(SimConfig::default());
+        exchange(&mut sim, UsbCmd::Airbag(AirbagCmd::SetInflationType(0x44)));
+        let resp = exchange(&mut sim, UsbCmd::Airbag(AirbagCmd::GetInflationType(0x00)));
+        assert_eq!(resp, UsbCmd::Airbag(AirbagCmd::GetInflationType(0x44)));
+
+        exchange(&mut sim, UsbCmd::General(GeneralCmd::SetCustomerInfo(Setting::Value("J. Rider".to_string()))));
+        let date = ServiceDate::new(15, 3, 2024).unwrap();
+        exchange(&mut sim, UsbCmd::General(GeneralCmd::SetServiceDate(Setting::Value(date))));
+        let resp = exchange(&mut sim, UsbCmd::General(GeneralCmd::GetCustomerInfo(None)));
+        assert_eq!(resp, UsbCmd::General(GeneralCmd::GetCustomerInfo(Some("J. Rider".to_string()))));
+        let resp = exchange(&mut sim, UsbCmd::General(GeneralCmd::GetServiceDate(None)));
+        assert_eq!(resp, UsbCmd::General(GeneralCmd::GetServiceDate(Some(date))));
+    }
+    #[test]
+    fn counts_down_fw_pages() {
+        let mut sim = Simulator::new(SimConfig::default());
+        let chunk = [0xffu8; 256];
+        let fw_data = FWData::new(&chunk, &2).unwrap();
+        let resp = exchange(&mut sim, UsbCmd::SWUpdate(SWUpdateCmd::WriteFWData(fw_data)));
+        if let Some(SWUpdateCmd::WriteFWData(res)) = resp.swupdate() {
+            assert_eq!(res.page_count, 1);
+        } else {
+            panic!("expected WriteFWData");
+        }
+    }
+    #[test]
+    fn serves_over_pipe() {
+        let (host, mut device) = pipe();
+        thread::spawn(move || {
+            let mut sim = Simulator::new(SimConfig::default());
+            let _ = sim.serve(&mut device);
+        });
+        let mut ta = TechAir::with_transport(host);
+        ta.write(UsbCmd::Logging(LoggingCmd::GetBatCount(None))).unwrap();
+        assert_eq!(ta.read().unwrap(), UsbCmd::Logging(LoggingCmd::GetBatCount(Some(0))));
+    }
+}
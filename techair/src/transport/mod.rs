@@ -0,0 +1,74 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use std::io;
+use std::time::Duration;
+
+mod serial;
+mod pipe;
+mod replay;
+#[cfg(unix)]
+mod pty;
+
+pub use self::serial::SerialTransport;
+pub use self::pipe::{pipe, PipeTransport};
+pub use self::replay::ReplayTransport;
+#[cfg(unix)]
+pub use self::pty::PtyTransport;
+
+/// A byte stream the ACU protocol can be spoken over.
+///
+/// `TechAir` only ever needs to push a complete encoded `UsbCmd` out and
+/// pull bytes back in, so anything from the real UART to an in-memory
+/// pipe can stand in for the device.
+pub trait Transport {
+    /// Writes one complete, already encoded frame.
+    fn write_frame(&mut self, frame: &[u8]) -> io::Result<()>;
+
+    /// Reads whatever bytes are available into `buf`, failing with
+    /// `ErrorKind::TimedOut` if nothing arrives within the current timeout.
+    ///
+    /// There is no notion of a frame at this level, see `framing::Framer`
+    /// for splitting the stream back up.
+    fn read_bytes(&mut self, buf: &mut [u8]) -> io::Result<usize>;
+
+    fn set_timeout(&mut self, timeout: Duration) -> io::Result<()>;
+
+    /// Discards any received bytes that have not been read yet.
+    fn clear(&mut self) -> io::Result<()>;
+
+    fn name(&self) -> Option<String> {
+        None
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn write_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        (**self).write_frame(frame)
+    }
+    fn read_bytes(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (**self).read_bytes(buf)
+    }
+    fn set_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        (**self).set_timeout(timeout)
+    }
+    fn clear(&mut self) -> io::Result<()> {
+        (**self).clear()
+    }
+    fn name(&self) -> Option<String> {
+        (**self).name()
+    }
+}
@@ -0,0 +1,131 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use std::collections::VecDeque;
+use std::io;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use crate::transport::Transport;
+
+
+#[derive(Default)]
+struct Channel {
+    buf: Mutex<VecDeque<u8>>,
+    ready: Condvar,
+}
+
+/// One end of an in-memory, full-duplex byte pipe.
+///
+/// Whatever is written on one end is read back on the other, which lets
+/// a test thread play the part of the ACU.
+pub struct PipeTransport {
+    rx: Arc<Channel>,
+    tx: Arc<Channel>,
+    timeout: Duration,
+}
+
+/// Creates a connected pair of pipe ends, host side first.
+pub fn pipe() -> (PipeTransport, PipeTransport) {
+    let a = Arc::new(Channel::default());
+    let b = Arc::new(Channel::default());
+    let host = PipeTransport{
+        rx: a.clone(),
+        tx: b.clone(),
+        timeout: Duration::from_millis(100),
+    };
+    let device = PipeTransport{
+        rx: b,
+        tx: a,
+        timeout: Duration::from_millis(100),
+    };
+    (host, device)
+}
+
+fn lock(ch: &Channel) -> io::Result<std::sync::MutexGuard<'_, VecDeque<u8>>> {
+    ch.buf.lock()
+        .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "pipe poisoned"))
+}
+
+impl Transport for PipeTransport {
+    fn write_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        lock(&self.tx)?.extend(frame);
+        self.tx.ready.notify_all();
+        Ok(())
+    }
+
+    fn read_bytes(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let buf = lock(&self.rx)?;
+        let (mut buf, _) = self.rx.ready
+            .wait_timeout_while(buf, self.timeout, |b| b.is_empty())
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "pipe poisoned"))?;
+        if buf.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "no bytes returned"));
+        }
+        let n = out.len().min(buf.len());
+        for (o, b) in out.iter_mut().zip(buf.drain(..n)) {
+            *o = b;
+        }
+        Ok(n)
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        self.timeout = timeout;
+        Ok(())
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        lock(&self.rx)?.clear();
+        Ok(())
+    }
+
+    fn name(&self) -> Option<String> {
+        Some("pipe".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let (mut host, mut device) = pipe();
+        let mut buf = [0u8; 8];
+        host.write_frame(&[0x00,0x02,0x80,0x71]).unwrap();
+        assert_eq!(device.read_bytes(&mut buf).unwrap(), 4);
+        assert_eq!(buf[..4], [0x00,0x02,0x80,0x71]);
+        device.write_frame(&[0x00,0x02,0x01,0x17]).unwrap();
+        assert_eq!(host.read_bytes(&mut buf[..2]).unwrap(), 2);
+        assert_eq!(host.read_bytes(&mut buf[2..]).unwrap(), 2);
+        assert_eq!(buf[..4], [0x00,0x02,0x01,0x17]);
+    }
+    #[test]
+    fn read_times_out() {
+        let (mut host, _device) = pipe();
+        host.set_timeout(Duration::from_millis(1)).unwrap();
+        let err = host.read_bytes(&mut [0u8; 8]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+    #[test]
+    fn clear_drops_pending() {
+        let (mut host, mut device) = pipe();
+        device.write_frame(&[0xde,0xad]).unwrap();
+        host.clear().unwrap();
+        host.set_timeout(Duration::from_millis(1)).unwrap();
+        assert!(host.read_bytes(&mut [0u8; 8]).is_err());
+    }
+}
@@ -0,0 +1,87 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+extern crate serialport;
+
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use serialport::posix::TTYPort;
+
+use crate::transport::{SerialTransport, Transport};
+
+
+/// A pseudo-terminal, either end of it.
+///
+/// Opening a slave path (e.g. `/dev/pts/3`) behaves exactly like talking
+/// to the real UART, while `pair()` hands out the master end as well so
+/// something can sit on the other side and answer like an ACU.
+pub struct PtyTransport {
+    inner: SerialTransport,
+    path: Option<String>,
+}
+
+impl PtyTransport {
+    pub fn open(path: &str) -> Result<PtyTransport, serialport::Error> {
+    	let s = serialport::SerialPortSettings{
+    		baud_rate: 115200,
+    		timeout: Duration::from_millis(100),
+    		..Default::default()
+    	};
+        let tty = TTYPort::open(Path::new(path), &s)?;
+        Ok(PtyTransport{
+            inner: SerialTransport::from_port(Box::new(tty)),
+            path: Some(path.to_string()),
+        })
+    }
+
+    /// Allocates a new pty and returns its (master, slave) ends.
+    ///
+    /// The slave must be kept alive for as long as the master is used,
+    /// reads on the master fail once every slave fd has been closed.
+    pub fn pair() -> Result<(PtyTransport, PtyTransport), serialport::Error> {
+        let (master, slave) = TTYPort::pair()?;
+        let path = serialport::SerialPort::name(&slave);
+        Ok((
+            PtyTransport{ inner: SerialTransport::from_port(Box::new(master)), path: None },
+            PtyTransport{ inner: SerialTransport::from_port(Box::new(slave)), path },
+        ))
+    }
+
+    /// The /dev/pts path of this end, if it is a slave.
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+}
+
+impl Transport for PtyTransport {
+    fn write_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        self.inner.write_frame(frame)
+    }
+    fn read_bytes(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read_bytes(buf)
+    }
+    fn set_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        self.inner.set_timeout(timeout)
+    }
+    fn clear(&mut self) -> io::Result<()> {
+        self.inner.clear()
+    }
+    fn name(&self) -> Option<String> {
+        self.path.clone()
+    }
+}
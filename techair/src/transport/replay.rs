@@ -0,0 +1,132 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+
+use std::collections::VecDeque;
+use std::io;
+use std::time::Duration;
+
+use crate::capture::{load_capture, CaptureEntry};
+use crate::framing::Direction;
+use crate::transport::Transport;
+
+
+/// Plays a capture back in place of the ACU.
+///
+/// Every frame written has to match the next request in the capture, the
+/// responses recorded after it are then handed out to the reader. So a
+/// capture taken from a real unit re-runs the exact same exchange and a
+/// diverging request fails loudly rather than getting a bogus reply.
+pub struct ReplayTransport {
+    entries: VecDeque<CaptureEntry>,
+    rx: VecDeque<u8>,
+    name: String,
+}
+
+impl ReplayTransport {
+    pub fn new(entries: Vec<CaptureEntry>) -> ReplayTransport {
+        ReplayTransport{
+            entries: entries.into(),
+            rx: VecDeque::new(),
+            name: "replay".to_string(),
+        }
+    }
+
+    pub fn open(path: &str) -> io::Result<ReplayTransport> {
+        let mut r = ReplayTransport::new(load_capture(path)?);
+        r.name = format!("replay:{}", path);
+        Ok(r)
+    }
+
+    /// True once every recorded request has been replayed.
+    pub fn is_done(&self) -> bool {
+        !self.entries.iter().any(|e| e.dir == Direction::Request)
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn write_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        // responses that were never read, e.g. after a timeout.
+        while matches!(self.entries.front(), Some(e) if e.dir == Direction::Response) {
+            self.entries.pop_front();
+        }
+        let req = self.entries.pop_front().ok_or_else(||
+            io::Error::new(io::ErrorKind::UnexpectedEof, "capture exhausted"))?;
+        if req.frame != frame {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                format!("capture expected {:02x?} but got {:02x?}", req.frame, frame)));
+        }
+        while matches!(self.entries.front(), Some(e) if e.dir == Direction::Response) {
+            if let Some(e) = self.entries.pop_front() {
+                self.rx.extend(e.frame);
+            }
+        }
+        Ok(())
+    }
+
+    fn read_bytes(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.rx.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "no bytes returned"));
+        }
+        let n = buf.len().min(self.rx.len());
+        for (o, b) in buf.iter_mut().zip(self.rx.drain(..n)) {
+            *o = b;
+        }
+        Ok(n)
+    }
+
+    fn set_timeout(&mut self, _timeout: Duration) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        self.rx.clear();
+        Ok(())
+    }
+
+    fn name(&self) -> Option<String> {
+        Some(self.name.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(dir: Direction, frame: &[u8]) -> CaptureEntry {
+        CaptureEntry{ at: Duration::from_millis(0), dir, frame: frame.to_vec() }
+    }
+
+    #[test]
+    fn replays_in_order() {
+        let mut r = ReplayTransport::new(vec![
+            entry(Direction::Request,  &[0x00,0x02,0x80,0x71]),
+            entry(Direction::Response, &[0x00,0x02,0x01,0x31,0xdd,0x3b]),
+        ]);
+        let mut buf = [0u8; 8];
+        assert_eq!(r.read_bytes(&mut buf).unwrap_err().kind(), io::ErrorKind::TimedOut);
+        r.write_frame(&[0x00,0x02,0x80,0x71]).unwrap();
+        assert_eq!(r.read_bytes(&mut buf).unwrap(), 6);
+        assert_eq!(buf[..6], [0x00,0x02,0x01,0x31,0xdd,0x3b]);
+        assert!(r.is_done());
+        assert_eq!(r.write_frame(&[0x00,0x02,0x80,0x71]).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+    #[test]
+    fn diverging_request_fails() {
+        let mut r = ReplayTransport::new(vec![entry(Direction::Request, &[0x00,0x02,0x80,0x71])]);
+        assert_eq!(r.write_frame(&[0x00,0x04,0x00,0x73]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
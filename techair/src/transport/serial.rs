@@ -0,0 +1,86 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+extern crate serialport;
+
+use std::io;
+use std::io::prelude::*;
+use std::time::Duration;
+
+use crate::transport::Transport;
+
+
+pub struct SerialTransport {
+    // keep a boxed heap allocation of the trait SerialPort
+    uart: Box<dyn serialport::SerialPort>,
+}
+
+impl SerialTransport {
+    /// 115200 8N1 without flow control, which is what the ACU speaks.
+    pub fn default_settings() -> serialport::SerialPortSettings {
+    	serialport::SerialPortSettings{
+    		baud_rate: 115200,
+    		data_bits: serialport::DataBits::Eight,
+    		flow_control: serialport::FlowControl::None,
+    		parity: serialport::Parity::None,
+    		stop_bits: serialport::StopBits::One,
+    		timeout: Duration::from_millis(100) // 2000 R, 500 W ?
+    	}
+    }
+
+    pub fn open(path: &str) -> Result<SerialTransport, serialport::Error> {
+        SerialTransport::open_with_settings(path, &SerialTransport::default_settings())
+    }
+
+    pub fn open_with_settings(path: &str, s: &serialport::SerialPortSettings)
+        -> Result<SerialTransport, serialport::Error> {
+        let uart = serialport::open_with_settings(path, s)?;
+        uart.clear(serialport::ClearBuffer::All)?;
+        Ok(SerialTransport::from_port(uart))
+    }
+
+    pub fn from_port(uart: Box<dyn serialport::SerialPort>) -> SerialTransport {
+        SerialTransport{ uart }
+    }
+
+    pub fn port(&self) -> &dyn serialport::SerialPort {
+        self.uart.as_ref()
+    }
+}
+
+impl Transport for SerialTransport {
+    fn write_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        self.uart.write_all(frame)
+    }
+
+    fn read_bytes(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.uart.read(buf)
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        self.uart.set_timeout(timeout)?;
+        Ok(())
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        self.uart.clear(serialport::ClearBuffer::Input)?;
+        Ok(())
+    }
+
+    fn name(&self) -> Option<String> {
+        self.uart.name()
+    }
+}
@@ -0,0 +1,409 @@
+// Copyright (C) 2020, Edward O'Callaghan.
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+
+// Reads usbmon traces, e.g. from Wireshark or `tcpdump -i usbmon1`, taken
+// while the vendor tool talks to an ACU, and turns them back into frames.
+//
+// Both the classic pcap and the pcapng container are understood, with the
+// LINKTYPE_USB_LINUX (48 byte) and LINKTYPE_USB_LINUX_MMAPPED (64 byte)
+// packet headers. Only bulk transfers carry the CDC-ACM data: host -> ACU
+// bytes sit in the OUT submissions, ACU -> host bytes in the IN completions.
+// The usbmon header is in the byte order of the machine that recorded it,
+// which is also the one the file was written in.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::time::Duration;
+
+use crate::capture::CaptureEntry;
+use crate::frame::Direction;
+use crate::framing::Framer;
+
+
+const LINKTYPE_USB_LINUX: u32 = 189;
+const LINKTYPE_USB_LINUX_MMAPPED: u32 = 220;
+
+const PCAPNG_SHB: u32 = 0x0a0d_0d0a;
+const PCAPNG_IDB: u32 = 0x0000_0001;
+const PCAPNG_SPB: u32 = 0x0000_0003;
+const PCAPNG_EPB: u32 = 0x0000_0006;
+
+const XFER_BULK: u8 = 3;
+
+/// Bytes moved by one bulk transfer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UsbChunk {
+    pub at: Duration,
+    pub bus: u16,
+    pub dev: u8,
+    pub dir: Direction,
+    pub data: Vec<u8>,
+}
+
+fn bad(why: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, why.to_string())
+}
+
+#[derive(Clone, Copy)]
+struct Bytes<'a> {
+    buf: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Bytes<'a> {
+    fn u16_at(&self, at: usize) -> Option<u16> {
+        let b = [*self.buf.get(at)?, *self.buf.get(at + 1)?];
+        Some(if self.big_endian { u16::from_be_bytes(b) } else { u16::from_le_bytes(b) })
+    }
+
+    fn u32_at(&self, at: usize) -> Option<u32> {
+        let b = [*self.buf.get(at)?, *self.buf.get(at + 1)?, *self.buf.get(at + 2)?, *self.buf.get(at + 3)?];
+        Some(if self.big_endian { u32::from_be_bytes(b) } else { u32::from_le_bytes(b) })
+    }
+}
+
+// ticks of `per_sec` a second since the epoch.
+fn ticks(t: u64, per_sec: u64) -> Duration {
+    let nanos = (t % per_sec) as u128 * 1_000_000_000 / per_sec as u128;
+    Duration::new(t / per_sec, nanos as u32)
+}
+
+// picks the bulk data out of one usbmon packet.
+fn usb_chunk(linktype: u32, at: Duration, p: Bytes) -> Option<UsbChunk> {
+    let header = match linktype {
+        LINKTYPE_USB_LINUX => 48,
+        LINKTYPE_USB_LINUX_MMAPPED => 64,
+        _ => return None,
+    };
+    let (kind, xfer, ep, dev) = (*p.buf.get(8)?, *p.buf.get(9)?, *p.buf.get(10)?, *p.buf.get(11)?);
+    let bus = p.u16_at(12)?;
+    let len_cap = p.u32_at(36)? as usize;
+    if xfer != XFER_BULK || len_cap == 0 {
+        return None;
+    }
+    let dir = match (kind, ep & 0x80 != 0) {
+        (b'S', false) => Direction::Request,
+        (b'C', true)  => Direction::Response,
+        _ => return None,
+    };
+    let end = (header + len_cap).min(p.buf.len());
+    let data = p.buf.get(header..end)?.to_vec();
+    Some(UsbChunk{ at, bus, dev, dir, data })
+}
+
+fn read_pcap(buf: &[u8]) -> io::Result<Vec<UsbChunk>> {
+    let magic = u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]);
+    let (big_endian, per_sec) = match magic {
+        0xa1b2_c3d4 => (false, 1_000_000),
+        0xa1b2_3c4d => (false, 1_000_000_000),
+        0xd4c3_b2a1 => (true, 1_000_000),
+        0x4d3c_b2a1 => (true, 1_000_000_000),
+        _ => return Err(bad("not a pcap or pcapng file")),
+    };
+    let file = Bytes{ buf, big_endian };
+    let linktype = file.u32_at(20).ok_or_else(|| bad("truncated pcap header"))?;
+    if linktype != LINKTYPE_USB_LINUX && linktype != LINKTYPE_USB_LINUX_MMAPPED {
+        return Err(bad(&format!("link type {} is not usbmon", linktype)));
+    }
+
+    let mut chunks = Vec::new();
+    let mut at = 24;
+    while at < buf.len() {
+        let rec = (file.u32_at(at), file.u32_at(at + 4), file.u32_at(at + 8));
+        let (secs, frac, len) = match rec {
+            (Some(s), Some(f), Some(l)) if at + 16 + l as usize <= buf.len() => (s, f, l as usize),
+            _ => {
+                warn!("pcap: dropping truncated record at offset {}", at);
+                break;
+            },
+        };
+        let ts = Duration::from_secs(secs as u64) + ticks(frac as u64, per_sec);
+        let p = Bytes{ buf: &buf[(at + 16)..(at + 16 + len)], big_endian };
+        chunks.extend(usb_chunk(linktype, ts, p));
+        at += 16 + len;
+    }
+    Ok(chunks)
+}
+
+// the if_tsresol option of an interface description, in ticks a second.
+fn tsresol(opts: Bytes) -> u64 {
+    let mut at = 0;
+    while let (Some(code), Some(len)) = (opts.u16_at(at), opts.u16_at(at + 2)) {
+        if code == 0 {
+            break;
+        }
+        if code == 9 && len == 1 {
+            if let Some(&v) = opts.buf.get(at + 4) {
+                return if v & 0x80 == 0 {
+                    10u64.checked_pow(v as u32).unwrap_or(1_000_000)
+                } else {
+                    1u64.checked_shl((v & 0x7f) as u32).unwrap_or(1_000_000)
+                };
+            }
+        }
+        at += 4 + ((len as usize + 3) & !3);
+    }
+    1_000_000
+}
+
+fn read_pcapng(buf: &[u8]) -> io::Result<Vec<UsbChunk>> {
+    // (link type, ticks a second) for each interface of the current section.
+    let mut ifaces: Vec<(u32, u64)> = Vec::new();
+    let mut big_endian = false;
+    let mut last = Duration::default();
+    let mut chunks = Vec::new();
+
+    let mut at = 0;
+    while at + 12 <= buf.len() {
+        if u32::from_le_bytes([buf[at], buf[at + 1], buf[at + 2], buf[at + 3]]) == PCAPNG_SHB {
+            big_endian = match buf.get((at + 8)..(at + 12)) {
+                Some([0x1a, 0x2b, 0x3c, 0x4d]) => true,
+                Some([0x4d, 0x3c, 0x2b, 0x1a]) => false,
+                _ => return Err(bad("bad pcapng byte order magic")),
+            };
+            ifaces.clear();
+        }
+        let file = Bytes{ buf, big_endian };
+        let kind = file.u32_at(at).unwrap_or_default();
+        let len = file.u32_at(at + 4).unwrap_or_default() as usize;
+        if len < 12 || len & 3 != 0 || at + len > buf.len() {
+            warn!("pcapng: dropping truncated block at offset {}", at);
+            break;
+        }
+        let body = Bytes{ buf: &buf[(at + 8)..(at + len - 4)], big_endian };
+        match kind {
+            PCAPNG_IDB => {
+                let linktype = body.u16_at(0).unwrap_or_default() as u32;
+                let opts = Bytes{ buf: body.buf.get(8..).unwrap_or_default(), big_endian };
+                ifaces.push((linktype, tsresol(opts)));
+            },
+            PCAPNG_EPB => {
+                let fields = (body.u32_at(0), body.u32_at(4), body.u32_at(8), body.u32_at(12));
+                if let (Some(iface), Some(hi), Some(lo), Some(caplen)) = fields {
+                    let &(linktype, per_sec) = ifaces.get(iface as usize)
+                        .ok_or_else(|| bad("packet on an undescribed interface"))?;
+                    let data = body.buf.get(20..(20 + caplen as usize))
+                        .ok_or_else(|| bad("packet longer than its block"))?;
+                    last = ticks((hi as u64) << 32 | lo as u64, per_sec);
+                    chunks.extend(usb_chunk(linktype, last, Bytes{ buf: data, big_endian }));
+                }
+            },
+            PCAPNG_SPB => {
+                // no interface id nor timestamp, it is interface 0 at the
+                // time of the packet before.
+                if let (Some(&(linktype, _)), Some(data)) = (ifaces.first(), body.buf.get(4..)) {
+                    chunks.extend(usb_chunk(linktype, last, Bytes{ buf: data, big_endian }));
+                }
+            },
+            _ => {},
+        }
+        at += len;
+    }
+    Ok(chunks)
+}
+
+/// Pulls the bulk transfers out of a pcap or pcapng usbmon trace.
+pub fn read_usbmon(buf: &[u8]) -> io::Result<Vec<UsbChunk>> {
+    if buf.len() < 24 {
+        return Err(bad("too short for a pcap file"));
+    }
+    if buf[..4] == PCAPNG_SHB.to_le_bytes() {
+        read_pcapng(buf)
+    } else {
+        read_pcap(buf)
+    }
+}
+
+pub fn load_usbmon(path: &str) -> io::Result<Vec<UsbChunk>> {
+    read_usbmon(&fs::read(path)?)
+}
+
+/// Reassembles the frames sent either way, per device, timed from the first
+/// transfer. The link is half duplex, so a new request ends whatever reply
+/// was still being put together.
+pub fn reassemble(chunks: &[UsbChunk]) -> Vec<CaptureEntry> {
+    let start = chunks.first().map_or(Duration::default(), |c| c.at);
+    let mut framers: BTreeMap<(u16, u8), (Framer, Framer)> = BTreeMap::new();
+    let mut entries = Vec::new();
+
+    for c in chunks {
+        let at = c.at.checked_sub(start).unwrap_or_default();
+        let (req, resp) = framers.entry((c.bus, c.dev))
+            .or_insert_with(|| (Framer::new(Direction::Request), Framer::new(Direction::Response)));
+        let framer = match c.dir {
+            Direction::Request => {
+                if !resp.is_empty() {
+                    entries.push(CaptureEntry{ at, dir: Direction::Response, frame: resp.take_remaining() });
+                }
+                req
+            },
+            Direction::Response => resp,
+        };
+        framer.push(&c.data);
+        while let Some(frame) = framer.next_frame() {
+            entries.push(CaptureEntry{ at, dir: c.dir, frame });
+        }
+    }
+    let end = chunks.last().map_or(Duration::default(), |c| c.at.checked_sub(start).unwrap_or_default());
+    for (req, resp) in framers.values_mut() {
+        if !req.is_empty() {
+            entries.push(CaptureEntry{ at: end, dir: Direction::Request, frame: req.take_remaining() });
+        }
+        if !resp.is_empty() {
+            entries.push(CaptureEntry{ at: end, dir: Direction::Response, frame: resp.take_remaining() });
+        }
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::prelude::*;
+    use crate::encoder::{TechAirEncoder, TechAirResponseEncoder};
+    use crate::usbcmd::UsbCmd;
+
+    fn packet(kind: u8, xfer: u8, ep: u8, data: &[u8]) -> Vec<u8> {
+        let mut p = vec![0u8; 48];
+        p[8] = kind;
+        p[9] = xfer;
+        p[10] = ep;
+        p[11] = 7;
+        p[12..14].copy_from_slice(&3u16.to_le_bytes());
+        p[32..36].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        p[36..40].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        p.extend_from_slice(data);
+        p
+    }
+
+    // an exchange of the vendor tool: the request, the IN URB it queues for
+    // the answer, the answer in two pieces and some line coding in between.
+    type Exchange = (Vec<u8>, Vec<u8>, Vec<(u64, Vec<u8>)>);
+
+    fn exchange() -> Exchange {
+        let mut req = Vec::new();
+        UsbCmd::General(GeneralCmd::GetSerialNr(None)).write_bytes(&mut req);
+        let mut resp = Vec::new();
+        UsbCmd::General(GeneralCmd::GetSerialNr(Some("TA0000001".to_string())))
+            .write_response_bytes(&mut resp);
+        let packets = vec![
+            (1_000_000, packet(b'S', 2, 0x00, &[0x80, 0x20, 0, 0, 0, 0, 0, 0])),
+            (1_000_100, packet(b'S', XFER_BULK, 0x02, &req)),
+            (1_000_200, packet(b'C', XFER_BULK, 0x02, &[])),
+            (1_000_300, packet(b'S', XFER_BULK, 0x81, &[])),
+            (1_002_000, packet(b'C', XFER_BULK, 0x81, &resp[..5])),
+            (1_002_500, packet(b'C', XFER_BULK, 0x81, &resp[5..])),
+        ];
+        (req, resp, packets)
+    }
+
+    fn check(chunks: &[UsbChunk], req: &[u8], resp: &[u8]) {
+        assert_eq!(chunks.len(), 3);
+        assert_eq!((chunks[0].bus, chunks[0].dev), (3, 7));
+        let entries = reassemble(chunks);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].dir, Direction::Request);
+        assert_eq!(entries[0].at, Duration::from_micros(0));
+        assert_eq!(entries[0].frame, req);
+        assert_eq!(entries[1].dir, Direction::Response);
+        assert_eq!(entries[1].at, Duration::from_micros(2400));
+        assert_eq!(entries[1].frame, resp);
+        match entries[1].decode().unwrap().cmd {
+            UsbCmd::General(GeneralCmd::GetSerialNr(Some(s))) => assert_eq!(s, "TA0000001"),
+            c => panic!("{:?}", c),
+        }
+    }
+
+    #[test]
+    fn classic_pcap() {
+        let (req, resp, packets) = exchange();
+        let mut file = Vec::new();
+        for v in &[0xa1b2_c3d4u32, 0x0004_0002, 0, 0, 65535, LINKTYPE_USB_LINUX] {
+            file.extend_from_slice(&v.to_le_bytes());
+        }
+        for (us, p) in &packets {
+            for v in &[(us / 1_000_000) as u32, (us % 1_000_000) as u32, p.len() as u32, p.len() as u32] {
+                file.extend_from_slice(&v.to_le_bytes());
+            }
+            file.extend_from_slice(p);
+        }
+        check(&read_usbmon(&file).unwrap(), &req, &resp);
+    }
+
+    fn block(file: &mut Vec<u8>, kind: u32, body: &[u8]) {
+        let len = 12 + ((body.len() + 3) & !3) as u32;
+        file.extend_from_slice(&kind.to_le_bytes());
+        file.extend_from_slice(&len.to_le_bytes());
+        file.extend_from_slice(body);
+        file.resize(file.len() + (len as usize - 12 - body.len()), 0);
+        file.extend_from_slice(&len.to_le_bytes());
+    }
+
+    #[test]
+    fn pcapng_in_nanoseconds() {
+        let (req, resp, packets) = exchange();
+        let mut file = Vec::new();
+        block(&mut file, PCAPNG_SHB, &[0x4d, 0x3c, 0x2b, 0x1a, 1, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        // usbmon, if_tsresol = 9, end of options.
+        block(&mut file, PCAPNG_IDB, &[189, 0, 0, 0, 0, 0, 0, 0, 9, 0, 1, 0, 9, 0, 0, 0, 0, 0, 0, 0]);
+        for (us, p) in &packets {
+            let ns = us * 1000;
+            let mut body = Vec::new();
+            for v in &[0, (ns >> 32) as u32, ns as u32, p.len() as u32, p.len() as u32] {
+                body.extend_from_slice(&v.to_le_bytes());
+            }
+            body.extend_from_slice(p);
+            block(&mut file, PCAPNG_EPB, &body);
+        }
+        check(&read_usbmon(&file).unwrap(), &req, &resp);
+    }
+
+    #[test]
+    fn tsresol_powers_of_two() {
+        let opts = [9, 0, 1, 0, 0x8a, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(tsresol(Bytes{ buf: &opts, big_endian: false }), 1024);
+        assert_eq!(ticks(1536, 1024), Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn new_request_ends_a_broken_reply() {
+        let chunk = |at, dir, data: &[u8]| UsbChunk{
+            at: Duration::from_millis(at), bus: 1, dev: 2, dir, data: data.to_vec(),
+        };
+        let chunks = [
+            chunk(0, Direction::Request, &[0x00, 0x02, 0x80, 0x71]),
+            chunk(1, Direction::Response, &[0x00, 0x02, 0x01]),
+            chunk(5, Direction::Request, &[0x00, 0x04, 0x00, 0x73]),
+        ];
+        let entries = reassemble(&chunks);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[1].dir, Direction::Response);
+        assert_eq!(entries[1].frame, [0x00, 0x02, 0x01]);
+        assert_eq!(entries[2].at, Duration::from_millis(5));
+    }
+
+    #[test]
+    fn not_usbmon() {
+        let mut file = Vec::new();
+        for v in &[0xa1b2_c3d4u32, 0x0004_0002, 0, 0, 65535, 1] {
+            file.extend_from_slice(&v.to_le_bytes());
+        }
+        assert_eq!(read_usbmon(&file).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert!(read_usbmon(&[0u8; 32]).is_err());
+    }
+}